@@ -1,7 +1,10 @@
 //! (TODO) Alternate Function I/O
 
+use crate::gpio::gpioa::{self, PA13, PA14, PA15};
+use crate::gpio::gpiob::{self, PB3, PB4};
+use crate::gpio::{Floating, Input, Jtag, JtagPin};
 use crate::pac::{afio, AFIO};
-use crate::rcu::APB2;
+use crate::rcu::{Enable, Reset, APB2};
 
 pub trait AfioExt {
     fn split(self, apb2: &mut APB2) -> Parts;
@@ -10,13 +13,16 @@ pub trait AfioExt {
 impl AfioExt for AFIO {
     fn split(self, apb2: &mut APB2) -> Parts {
         riscv::interrupt::free(|_| {
-            apb2.en().modify(|_, w| w.afen().set_bit());
-            apb2.rst().modify(|_, w| w.afrst().set_bit());
-            apb2.rst().modify(|_, w| w.afrst().clear_bit());
+            AFIO::enable(apb2);
+            AFIO::reset(apb2);
         });
         Parts {
             ec: EC { _ownership: () },
-            pcf0: PCF0 { _ownership: () },
+            extiss: ExtiSelect { _ownership: () },
+            pcf0: PCF0 {
+                swj_cfg: 0b000,
+                _ownership: (),
+            },
             pcf1: PCF1 { _ownership: () },
             _todo: (),
         }
@@ -25,27 +31,194 @@ impl AfioExt for AFIO {
 
 pub struct Parts {
     pub ec: EC, // pub ec: EventOutCtrl
-    // pub extiss: ExtiSelect
+    pub extiss: ExtiSelect,
     pub pcf0: PCF0,
     pub pcf1: PCF1,
     _todo: (),
 }
 
+/// Event control register
+///
+/// Constrains `EC`.
 pub struct EC {
     _ownership: (),
 }
 
-// todo: impl EC
+impl EC {
+    #[inline]
+    pub(crate) fn ec(&mut self) -> &afio::EC {
+        unsafe { &(*AFIO::ptr()).ec }
+    }
+
+    /// Output the event signal of the core on `pin` (EVENTOUT).
+    ///
+    /// A pulse is sent on the pin when the core executes an event-sending
+    /// instruction. Only one pin could be the event output at a time.
+    pub fn enable_event_output<P: EventOutPin>(&mut self, pin: P) -> EventOut<P> {
+        let (port, index) = pin.event_source();
+        self.ec().write(|w| unsafe {
+            w.port().bits(port);
+            w.pin().bits(index);
+            w.eoe().set_bit()
+        });
+        EventOut { pin }
+    }
+}
+
+#[doc(hidden)]
+pub(crate) mod private {
+    // Only GPIO pins of this crate could output the event signal
+    pub trait Sealed {}
+}
+
+/// GPIO pins which could output the event signal (EVENTOUT), in alternate
+/// push-pull mode
+pub trait EventOutPin: private::Sealed {
+    // Returns (port, pin number) of this pin, port 0 => GPIOA, ..., 4 => GPIOE
+    #[doc(hidden)]
+    fn event_source(&self) -> (u8, u8);
+}
+
+/// Event output pin, created by `EC::enable_event_output`
+pub struct EventOut<P> {
+    pin: P,
+}
+
+impl<P> EventOut<P> {
+    /// Disable the event output and release the pin.
+    pub fn release(self, ec: &mut EC) -> P {
+        ec.ec().modify(|_, w| w.eoe().clear_bit());
+        self.pin
+    }
+}
+
+/// EXTI source selection registers
+///
+/// Constrains `EXTISS0` to `EXTISS3`.
+pub struct ExtiSelect {
+    _ownership: (),
+}
+
+impl ExtiSelect {
+    // Connect EXTI line `line` to pin `line` of port `port` (0 => GPIOA, ...)
+    #[inline]
+    pub(crate) fn select(&mut self, line: u8, port: u8) {
+        let afio = unsafe { &*AFIO::ptr() };
+        let shift = (line % 4) * 4;
+        let bits = |r: u32| (r & !(0b1111 << shift)) | ((port as u32) << shift);
+        match line / 4 {
+            0 => afio
+                .extiss0
+                .modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            1 => afio
+                .extiss1
+                .modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            2 => afio
+                .extiss2
+                .modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+            _ => afio
+                .extiss3
+                .modify(|r, w| unsafe { w.bits(bits(r.bits())) }),
+        }
+    }
+}
+
+/// JTAG pins PA13, PA14, PA15, PB3 and PB4 claimed by the JTAG interface
+pub type JtagPins = (PA13<Jtag>, PA14<Jtag>, PA15<Jtag>, PB3<Jtag>, PB4<Jtag>);
+
+/// JTAG pins PA13, PA14, PA15, PB3 and PB4 released as floating input pins
+pub type ReleasedJtagPins = (
+    PA13<Input<Floating>>,
+    PA14<Input<Floating>>,
+    PA15<Input<Floating>>,
+    PB3<Input<Floating>>,
+    PB4<Input<Floating>>,
+);
 
 /// Opaque PCF0 register
 pub struct PCF0 {
+    // SWJ_CFG bits are write-only and read back undefined values, so they are
+    // kept here and written again on every modification of PCF0
+    swj_cfg: u8,
     _ownership: (),
 }
 
 impl PCF0 {
+    // Modifies PCF0 with `f`, keeping SWJ_CFG bits as its last written value
+    #[inline]
+    pub(crate) fn modify<F>(&mut self, f: F)
+    where
+        for<'w> F: FnOnce(&afio::pcf0::R, &'w mut afio::pcf0::W) -> &'w mut afio::pcf0::W,
+    {
+        let swj_cfg = self.swj_cfg;
+        let pcf0 = unsafe { &(*AFIO::ptr()).pcf0 };
+        pcf0.modify(|r, w| unsafe { f(r, w).swj_cfg().bits(swj_cfg) });
+    }
+
+    /// Enable the full JTAG interface (SWJ_CFG = 000), which is the reset state.
+    ///
+    /// PA13 (JTMS), PA14 (JTCK), PA15 (JTDI), PB3 (JTDO) and PB4 (NJTRST) are
+    /// claimed by JTAG, so they are returned in the `Jtag` state.
+    pub fn enable_jtag<M0, M1, M2, M3, M4>(
+        &mut self,
+        pa13: PA13<M0>,
+        pa14: PA14<M1>,
+        pa15: PA15<M2>,
+        pb3: PB3<M3>,
+        pb4: PB4<M4>,
+    ) -> JtagPins {
+        self.set_swj_cfg(0b000);
+        (
+            pa13.claim(),
+            pa14.claim(),
+            pa15.claim(),
+            pb3.claim(),
+            pb4.claim(),
+        )
+    }
+
+    /// Enable JTAG without NJTRST (SWJ_CFG = 001), release PB4 as a floating
+    /// input pin. Other JTAG pins stay claimed.
+    pub fn disable_njtrst(
+        &mut self,
+        pb4: PB4<Jtag>,
+        gpiob_ctl0: &mut gpiob::CTL0,
+    ) -> PB4<Input<Floating>> {
+        self.set_swj_cfg(0b001);
+        pb4.release(gpiob_ctl0)
+    }
+
+    /// Disable the JTAG interface (SWJ_CFG = 100), release PA13, PA14, PA15, PB3
+    /// and PB4 as floating input pins, configured through the CTL registers of
+    /// their ports.
+    ///
+    /// PB4 could be in any mode, as it may be already released by `disable_njtrst`.
+    /// The core could not be debugged through JTAG after this function is called,
+    /// until the next reset.
+    pub fn disable_jtag<M4>(
+        &mut self,
+        pa13: PA13<Jtag>,
+        pa14: PA14<Jtag>,
+        pa15: PA15<Jtag>,
+        pb3: PB3<Jtag>,
+        pb4: PB4<M4>,
+        gpioa_ctl1: &mut gpioa::CTL1,
+        gpiob_ctl0: &mut gpiob::CTL0,
+    ) -> ReleasedJtagPins {
+        self.set_swj_cfg(0b100);
+        (
+            pa13.release(gpioa_ctl1),
+            pa14.release(gpioa_ctl1),
+            pa15.release(gpioa_ctl1),
+            pb3.release(gpiob_ctl0),
+            pb4.release(gpiob_ctl0),
+        )
+    }
+
     #[inline]
-    pub(crate) fn pcf0(&mut self) -> &afio::PCF0 {
-        unsafe { &(*AFIO::ptr()).pcf0 }
+    fn set_swj_cfg(&mut self, bits: u8) {
+        self.swj_cfg = bits;
+        self.modify(|_, w| w);
     }
 }
 
@@ -1,7 +1,7 @@
 //! (TODO) Backup register domain
 
 use crate::pac::{bkp, BKP, PMU};
-use crate::rcu::APB1;
+use crate::rcu::{Enable, APB1};
 use core::marker::PhantomData;
 
 /// Extension trait that constrains the `BKP` peripheral
@@ -19,8 +19,8 @@ impl BkpExt for BKP {
         // to registers and RTC.
         riscv::interrupt::free(|_| {
             // 1. use apb1 to enable backup domain clock
-            apb1.en()
-                .modify(|_, w| w.pmuen().set_bit().bkpien().set_bit());
+            PMU::enable(apb1);
+            BKP::enable(apb1);
             // 2. use pmuctl to enbale write access
             // todo: should PMU be designed as a separate module?
             pmu.ctl.write(|w| w.bkpwen().set_bit());
@@ -13,7 +13,7 @@
 //! todo: verify this module
 
 use crate::pac::CRC;
-use crate::rcu::AHB;
+use crate::rcu::{Enable, AHB};
 
 /// Read the value of the free data register `fdata`.
 #[inline]
@@ -43,7 +43,7 @@ impl Crc {
     /// Take ownership of CRC and enable CRC clock.
     #[inline]
     pub fn crc(crc: CRC, ahb: &mut AHB) -> Self {
-        ahb.en().modify(|_, w| w.crcen().set_bit());
+        CRC::enable(ahb);
         Crc { crc }
     }
 
@@ -59,7 +59,7 @@ impl Crc {
     /// Disable the CRC clock and release the peripheral.
     #[inline]
     pub fn release(self, ahb: &mut AHB) -> CRC {
-        ahb.en().modify(|_, w| w.crcen().clear_bit());
+        CRC::disable(ahb);
         self.crc
     }
 }
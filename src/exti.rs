@@ -0,0 +1,135 @@
+//! External Interrupt/Event Controller (EXTI)
+//!
+//! Ref: Section 9, the User Manual
+
+use crate::afio::ExtiSelect;
+use crate::pac::{exti, EXTI};
+
+/// Edges to trigger the interrupt or event
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Edge {
+    /// Rising edge
+    Rising,
+    /// Falling edge
+    Falling,
+    /// Both rising and falling edges
+    RisingFalling,
+}
+
+/// EXTI peripheral
+///
+/// Owns `INTEN`, `EVEN`, `RTEN`, `FTEN` and `SWIEV`. The pending register `PD`
+/// is shared with `ExtiPin` so that it could be cleared in interrupt handlers.
+pub struct Exti {
+    exti: EXTI,
+}
+
+impl Exti {
+    /// Wrap the EXTI peripheral
+    pub fn new(exti: EXTI) -> Self {
+        Exti { exti }
+    }
+
+    /// Release the EXTI peripheral
+    pub fn release(self) -> EXTI {
+        self.exti
+    }
+
+    #[inline]
+    pub(crate) fn regs(&mut self) -> &exti::RegisterBlock {
+        &self.exti
+    }
+}
+
+// Sets or clears bit `line` of an EXTI register
+macro_rules! set_line {
+    ($reg:expr, $line:expr, $value:expr) => {
+        $reg.modify(|r, w| unsafe {
+            if $value {
+                w.bits(r.bits() | (1 << $line))
+            } else {
+                w.bits(r.bits() & !(1 << $line))
+            }
+        })
+    };
+}
+
+#[doc(hidden)]
+pub(crate) mod private {
+    // Only GPIO input pins of this crate could be EXTI sources
+    pub trait Sealed {}
+}
+
+/// External interrupt and event functions for GPIO input pins
+pub trait ExtiPin: private::Sealed {
+    // Returns (port, line) of this pin, port 0 => GPIOA, ..., 4 => GPIOE
+    #[doc(hidden)]
+    fn exti_source(&self) -> (u8, u8);
+
+    /// Select this pin as the source of its EXTI line (AFIO EXTISSx).
+    ///
+    /// Each EXTI line is connected to pins of the same number, one port at a
+    /// time; e.g. PA3 and PB3 cannot be sources at the same time.
+    fn make_interrupt_source(&mut self, extiss: &mut ExtiSelect) {
+        let (port, line) = self.exti_source();
+        extiss.select(line, port);
+    }
+
+    /// Set the edges which trigger the interrupt or event of this line.
+    fn trigger_on_edge(&mut self, exti: &mut Exti, edge: Edge) {
+        let (_, line) = self.exti_source();
+        let (rising, falling) = match edge {
+            Edge::Rising => (true, false),
+            Edge::Falling => (false, true),
+            Edge::RisingFalling => (true, true),
+        };
+        let regs = exti.regs();
+        set_line!(regs.rten, line, rising);
+        set_line!(regs.ften, line, falling);
+    }
+
+    /// Enable the interrupt of this line.
+    fn enable_interrupt(&mut self, exti: &mut Exti) {
+        let (_, line) = self.exti_source();
+        set_line!(exti.regs().inten, line, true);
+    }
+
+    /// Disable the interrupt of this line.
+    fn disable_interrupt(&mut self, exti: &mut Exti) {
+        let (_, line) = self.exti_source();
+        set_line!(exti.regs().inten, line, false);
+    }
+
+    /// Enable the event of this line, which could wake up the core from `wfe`.
+    fn enable_event(&mut self, exti: &mut Exti) {
+        let (_, line) = self.exti_source();
+        set_line!(exti.regs().even, line, true);
+    }
+
+    /// Disable the event of this line.
+    fn disable_event(&mut self, exti: &mut Exti) {
+        let (_, line) = self.exti_source();
+        set_line!(exti.regs().even, line, false);
+    }
+
+    /// Trigger the interrupt or event of this line by software (SWIEV).
+    ///
+    /// The software trigger bit is cleared when the pending bit is cleared.
+    fn trigger_software(&mut self, exti: &mut Exti) {
+        let (_, line) = self.exti_source();
+        set_line!(exti.regs().swiev, line, true);
+    }
+
+    /// Returns whether the interrupt or event of this line is pending (PD).
+    fn check_interrupt(&self) -> bool {
+        let (_, line) = self.exti_source();
+        unsafe { &(*EXTI::ptr()).pd }.read().bits() & (1 << line) != 0
+    }
+
+    /// Clear the pending bit of this line.
+    fn clear_interrupt_pending_bit(&mut self) {
+        let (_, line) = self.exti_source();
+        // PD bits are cleared by writing 1, other bits are not affected
+        unsafe { &(*EXTI::ptr()).pd }.write(|w| unsafe { w.bits(1 << line) });
+    }
+}
@@ -1,16 +1,38 @@
 //! General Purpose Input / Output
 
+use crate::afio::EventOutPin;
+use crate::exti::ExtiPin;
+use crate::pac::{GPIOA, GPIOB, GPIOC, GPIOD, GPIOE};
 use crate::rcu::APB2;
+use core::convert::Infallible;
 use core::marker::PhantomData;
 use core::sync::atomic::{AtomicU32, Ordering};
+use embedded_hal::digital::v2::{InputPin, OutputPin, StatefulOutputPin, ToggleableOutputPin};
 
 /// Extension trait to split a GPIO peripheral into independent pins and registers
 pub trait GpioExt {
     /// The type to split the GPIO into
     type Parts;
 
+    /// The type to split the GPIO into, with pin modes preserved
+    type PreservedParts;
+
     /// Splits the GPIO block into independent pins and registers
+    ///
+    /// PA13, PA14, PA15, PB3 and PB4 are claimed by the JTAG interface after reset,
+    /// so they come out in the `Jtag` state.
     fn split(self, apb2: &mut APB2) -> Self::Parts;
+
+    /// Splits the GPIO block without resetting it, only enables its clock.
+    ///
+    /// Pin configurations left by a bootloader or previous program are kept,
+    /// so the pins come out in the `Unknown` mode. Use `try_into_mode` to check
+    /// the mode at runtime, or `assume_mode` if the mode is known; all `into_*`
+    /// functions are also available to reconfigure the pins.
+    ///
+    /// JTAG pins still come out in the `Jtag` state as `split` does, because
+    /// whether JTAG claims them can't be read back from AFIO.
+    fn split_preserving(self, apb2: &mut APB2) -> Self::PreservedParts;
 }
 
 /// Analog input mode (type state)
@@ -46,17 +68,128 @@ pub struct PushPull;
 /// Open drain output or alternate (type state)
 pub struct OpenDrain;
 
+/// Unknown mode of pins from `GpioExt::split_preserving` (type state)
+pub struct Unknown;
+
+/// Pin claimed by the JTAG interface (type state)
+///
+/// Pins in this state can't be configured or used for digital I/O; release them
+/// with `afio::PCF0::disable_njtrst` or `disable_jtag` first.
+pub struct Jtag;
+
+// Pins switched between GPIO and the JTAG interface by SWJ_CFG bits of AFIO
+pub(crate) trait JtagPin {
+    type Claimed;
+
+    type Released;
+
+    type Ctl;
+
+    // Changes the type state only, as JTAG takes over the pin regardless of CTL
+    fn claim(self) -> Self::Claimed;
+
+    // Resets CTL bits of the pin into floating input mode, its reset state
+    fn release(self, ctl: &mut Self::Ctl) -> Self::Released;
+}
+
 /// Marker trait for active states
 pub trait Active {}
 
 impl Active for Analog {}
 
+impl Active for Unknown {}
+
 impl<MODE> Active for Input<MODE> {}
 
 impl<MODE> Active for Output<MODE> {}
 
 impl<MODE> Active for Alternate<MODE> {}
 
+#[doc(hidden)]
+mod private {
+    // Pin modes and registers defined in this module only
+    pub trait Sealed {}
+}
+
+/// `CTL0` or `CTL1` register of a GPIO port, which configures pin modes
+pub trait CtlRegister: private::Sealed {
+    // Writes CTL[1:0] and MD[1:0] bits of pin `i` in this register
+    #[doc(hidden)]
+    fn set_ctl_md(&mut self, i: u8, ctl_and_md: u32);
+}
+
+/// Pin modes which could be checked at runtime
+pub trait PinMode: private::Sealed {
+    // Returns whether CTL[1:0] and MD[1:0] bits, and the OCTL bit of the pin
+    // match this mode
+    #[doc(hidden)]
+    fn matches(ctl_md: u32, octl: bool) -> bool;
+}
+
+impl private::Sealed for Analog {}
+
+impl PinMode for Analog {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md == 0b00_00
+    }
+}
+
+impl private::Sealed for Input<Floating> {}
+
+impl PinMode for Input<Floating> {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md == 0b01_00
+    }
+}
+
+impl private::Sealed for Input<PullDown> {}
+
+impl PinMode for Input<PullDown> {
+    fn matches(ctl_md: u32, octl: bool) -> bool {
+        ctl_md == 0b10_00 && !octl
+    }
+}
+
+impl private::Sealed for Input<PullUp> {}
+
+impl PinMode for Input<PullUp> {
+    fn matches(ctl_md: u32, octl: bool) -> bool {
+        ctl_md == 0b10_00 && octl
+    }
+}
+
+impl private::Sealed for Output<PushPull> {}
+
+impl PinMode for Output<PushPull> {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md >> 2 == 0b00 && ctl_md & 0b11 != 0
+    }
+}
+
+impl private::Sealed for Output<OpenDrain> {}
+
+impl PinMode for Output<OpenDrain> {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md >> 2 == 0b01 && ctl_md & 0b11 != 0
+    }
+}
+
+impl private::Sealed for Alternate<PushPull> {}
+
+impl PinMode for Alternate<PushPull> {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md >> 2 == 0b10 && ctl_md & 0b11 != 0
+    }
+}
+
+impl private::Sealed for Alternate<OpenDrain> {}
+
+impl PinMode for Alternate<OpenDrain> {
+    fn matches(ctl_md: u32, _: bool) -> bool {
+        ctl_md >> 2 == 0b11 && ctl_md & 0b11 != 0
+    }
+}
+
 /// Output speed up to 10 MHz (type param)
 pub struct UpTo10MHz;
 
@@ -85,6 +218,11 @@ impl Speed for UpTo2MHz {
     const MD_BITS: u32 = 0b10;
 }
 
+/// Error of the LOCK freeze process, where the LKK bit did not read back as
+/// expected; the pin modes may be not locked.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LockError;
+
 /// Wraps a pin if this pin is locked
 pub struct Locked<T>(T);
 
@@ -211,6 +349,334 @@ fn atomic_toggle_bit(r: &AtomicU32, index: usize) {
     r.fetch_xor(mask, Ordering::SeqCst);
 }
 
+/// Fully erased pin
+///
+/// Both the port and the pin number are stored at runtime, so that pins from
+/// different ports could be collected into one array.
+pub struct Pin<MODE> {
+    port: u8, // 0 => GPIOA, 1 => GPIOB, ..., 4 => GPIOE
+    i: u8,
+    _typestate_mode: PhantomData<MODE>,
+}
+
+impl<MODE> Pin<MODE> {
+    #[inline]
+    fn regs(&self) -> &'static crate::pac::gpioa::RegisterBlock {
+        let ptr = match self.port {
+            0 => GPIOA::ptr(),
+            1 => GPIOB::ptr(),
+            2 => GPIOC::ptr(),
+            3 => GPIOD::ptr(),
+            _ => GPIOE::ptr(),
+        };
+        unsafe { &*ptr }
+    }
+
+    #[inline]
+    fn istat_high(&self) -> bool {
+        self.regs().istat.read().bits() & (1 << self.i) != 0
+    }
+
+    #[inline]
+    fn octl_high(&self) -> bool {
+        self.regs().octl.read().bits() & (1 << self.i) != 0
+    }
+
+    #[inline]
+    fn set_octl(&self, is_high: bool) {
+        if is_high {
+            self.regs().bop.write(|w| unsafe { w.bits(1 << self.i) });
+        } else {
+            self.regs().bc.write(|w| unsafe { w.bits(1 << self.i) });
+        }
+    }
+
+    #[inline]
+    fn toggle_octl(&self) {
+        let r: &AtomicU32 = unsafe { &*(&self.regs().octl as *const _ as *const _) };
+        atomic_toggle_bit(r, self.i as usize);
+    }
+}
+
+impl<MODE> InputPin for Pin<Input<MODE>> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.istat_high())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.istat_high())
+    }
+}
+
+impl<MODE> crate::exti::private::Sealed for Pin<Input<MODE>> {}
+
+impl<MODE> ExtiPin for Pin<Input<MODE>> {
+    fn exti_source(&self) -> (u8, u8) {
+        (self.port, self.i)
+    }
+}
+
+impl crate::afio::private::Sealed for Pin<Alternate<PushPull>> {}
+
+impl EventOutPin for Pin<Alternate<PushPull>> {
+    fn event_source(&self) -> (u8, u8) {
+        (self.port, self.i)
+    }
+}
+
+impl InputPin for Pin<Output<OpenDrain>> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.istat_high())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.istat_high())
+    }
+}
+
+impl<MODE> OutputPin for Pin<Output<MODE>> {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set_octl(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set_octl(false);
+        Ok(())
+    }
+}
+
+impl<MODE> OutputPin for Pin<Alternate<MODE>> {
+    type Error = Infallible;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.set_octl(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.set_octl(false);
+        Ok(())
+    }
+}
+
+impl<MODE> StatefulOutputPin for Pin<Output<MODE>> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.octl_high())
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.octl_high())
+    }
+}
+
+impl<MODE> StatefulOutputPin for Pin<Alternate<MODE>> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        Ok(self.octl_high())
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        Ok(!self.octl_high())
+    }
+}
+
+impl<MODE> ToggleableOutputPin for Pin<Output<MODE>> {
+    type Error = Infallible;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self.toggle_octl();
+        Ok(())
+    }
+}
+
+impl<MODE> ToggleableOutputPin for Pin<Alternate<MODE>> {
+    type Error = Infallible;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        self.toggle_octl();
+        Ok(())
+    }
+}
+
+/// Mode of a `DynamicPin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DynamicMode {
+    /// Floating input
+    InputFloating,
+    /// Pulled up input
+    InputPullUp,
+    /// Pulled down input
+    InputPullDown,
+    /// Push-pull output
+    OutputPushPull,
+    /// Open drain output
+    OutputOpenDrain,
+}
+
+/// Error for digital operations not supported by the current mode of a `DynamicPin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ModeError {
+    /// The pin is not in an input mode, or open drain output mode
+    NotInput,
+    /// The pin is not in an output mode
+    NotOutput,
+    /// The pin mode is frozen by the LOCK register, so it can't be switched
+    Locked,
+}
+
+/// Pin with its mode switchable at runtime
+///
+/// This is useful for bidirectional lines, e.g. 1-Wire or DHT22 data pins. The
+/// output speed is set to the default value 50MHz in output modes. Digital traits
+/// return `ModeError` if they are not supported by the current mode.
+///
+/// Mode switching takes the `CTL0` or `CTL1` register of its port, like `into_*`
+/// functions of typed pins. It returns `ModeError::Locked` and keeps the current
+/// mode if the pin is frozen by the LOCK register of its port.
+pub struct DynamicPin<CTL> {
+    // the mode is stored in `mode`, rather than typestate of this pin
+    pin: Pin<()>,
+    mode: DynamicMode,
+    _ctl: PhantomData<CTL>,
+}
+
+impl<CTL: CtlRegister> DynamicPin<CTL> {
+    /// Returns the current mode of this pin.
+    pub fn mode(&self) -> DynamicMode {
+        self.mode
+    }
+
+    /// Switches the pin into floating input mode.
+    pub fn make_floating_input(&mut self, ctl: &mut CTL) -> Result<(), ModeError> {
+        self.set_mode(ctl, DynamicMode::InputFloating)
+    }
+
+    /// Switches the pin into pulled up input mode.
+    pub fn make_pull_up_input(&mut self, ctl: &mut CTL) -> Result<(), ModeError> {
+        self.set_mode(ctl, DynamicMode::InputPullUp)
+    }
+
+    /// Switches the pin into pulled down input mode.
+    pub fn make_pull_down_input(&mut self, ctl: &mut CTL) -> Result<(), ModeError> {
+        self.set_mode(ctl, DynamicMode::InputPullDown)
+    }
+
+    /// Switches the pin into push-pull output mode.
+    pub fn make_push_pull_output(&mut self, ctl: &mut CTL) -> Result<(), ModeError> {
+        self.set_mode(ctl, DynamicMode::OutputPushPull)
+    }
+
+    /// Switches the pin into open drain output mode.
+    pub fn make_open_drain_output(&mut self, ctl: &mut CTL) -> Result<(), ModeError> {
+        self.set_mode(ctl, DynamicMode::OutputOpenDrain)
+    }
+
+    fn set_mode(&mut self, ctl: &mut CTL, mode: DynamicMode) -> Result<(), ModeError> {
+        let regs = self.pin.regs();
+        // CTL0 and CTL1 bits of a pin ignore writes once LKK freezes its LKy bit
+        let lock = regs.lock.read().bits();
+        if lock & 0x00010000 != 0 && lock & (1 << self.pin.i) != 0 {
+            return Err(ModeError::Locked);
+        }
+        let ctl_and_md: u32 = match mode {
+            DynamicMode::InputFloating => 0b01_00,
+            DynamicMode::InputPullUp | DynamicMode::InputPullDown => 0b10_00,
+            DynamicMode::OutputPushPull => 0b00_00 | UpTo50MHz::MD_BITS,
+            DynamicMode::OutputOpenDrain => 0b01_00 | UpTo50MHz::MD_BITS,
+        };
+        // pull up or down is selected by the output control bit
+        match mode {
+            DynamicMode::InputPullUp => self.pin.set_octl(true),
+            DynamicMode::InputPullDown => self.pin.set_octl(false),
+            _ => {}
+        }
+        ctl.set_ctl_md(self.pin.i, ctl_and_md);
+        self.mode = mode;
+        Ok(())
+    }
+}
+
+impl<CTL> DynamicPin<CTL> {
+    #[inline]
+    fn is_input(&self) -> bool {
+        self.mode != DynamicMode::OutputPushPull
+    }
+
+    #[inline]
+    fn is_output(&self) -> bool {
+        matches!(
+            self.mode,
+            DynamicMode::OutputPushPull | DynamicMode::OutputOpenDrain
+        )
+    }
+}
+
+impl<CTL> InputPin for DynamicPin<CTL> {
+    type Error = ModeError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        if !self.is_input() {
+            return Err(ModeError::NotInput);
+        }
+        Ok(self.pin.istat_high())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|ans| !ans)
+    }
+}
+
+impl<CTL> OutputPin for DynamicPin<CTL> {
+    type Error = ModeError;
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        if !self.is_output() {
+            return Err(ModeError::NotOutput);
+        }
+        self.pin.set_octl(true);
+        Ok(())
+    }
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        if !self.is_output() {
+            return Err(ModeError::NotOutput);
+        }
+        self.pin.set_octl(false);
+        Ok(())
+    }
+}
+
+impl<CTL> StatefulOutputPin for DynamicPin<CTL> {
+    fn is_set_high(&self) -> Result<bool, Self::Error> {
+        if !self.is_output() {
+            return Err(ModeError::NotOutput);
+        }
+        Ok(self.pin.octl_high())
+    }
+
+    fn is_set_low(&self) -> Result<bool, Self::Error> {
+        self.is_set_high().map(|ans| !ans)
+    }
+}
+
+impl<CTL> ToggleableOutputPin for DynamicPin<CTL> {
+    type Error = ModeError;
+
+    fn toggle(&mut self) -> Result<(), Self::Error> {
+        if !self.is_output() {
+            return Err(ModeError::NotOutput);
+        }
+        self.pin.toggle_octl();
+        Ok(())
+    }
+}
+
 trait PinIndex {
     const OP_LK_INDEX: usize;
 
@@ -218,17 +684,20 @@ trait PinIndex {
 }
 
 macro_rules! impl_gpio {
-    ($GPIOX:ident,$gpiox:ident,$gpioy:ident,$en:ident,$rst: ident,$PXx:ident, [
-        $($PXi:ident:($pxi:ident,$i:expr,$MODE:ty,$CTL:ident,$ctl:ident),)+
+    ($GPIOX:ident,$gpiox:ident,$gpioy:ident,$port:expr,$PXx:ident, [
+        $($PXi:ident:($pxi:ident,$i:expr,$MODE:ty,$PMODE:ty,$CTL:ident,$ctl:ident),)+
     ]) => {
 /// GPIO port
 pub mod $gpiox {
     use super::{
         Active, Alternate, Analog, Floating, GpioExt, Input, OpenDrain, Output,
-        PinIndex, PullDown, PullUp, PushPull, Speed, UpTo50MHz, Locked, Unlock
+        CtlRegister, DynamicMode, DynamicPin, Jtag, JtagPin, LockError, Pin, PinIndex, PinMode,
+        PullDown, PullUp, PushPull, Speed, Unknown, UpTo50MHz, Locked, Unlock
     };
+    use crate::afio::EventOutPin;
+    use crate::exti::ExtiPin;
     use crate::pac::{$gpioy, $GPIOX};
-    use crate::rcu::APB2;
+    use crate::rcu::{Enable, Reset, APB2};
     use core::convert::Infallible;
     use core::marker::PhantomData;
     use core::sync::atomic::AtomicU32;
@@ -252,14 +721,52 @@ pub mod $gpiox {
         _extensible: (),
     }
 
+    /// GPIO parts with pin modes preserved
+    pub struct PreservedParts {
+        /// Opaque CTL0 register
+        pub ctl0: CTL0,
+        /// Opaque CTL1 register
+        pub ctl1: CTL1,
+        /// Opaque OCTL register
+        pub octl: OCTL,
+        /// Opaque LOCK register
+        pub lock: LOCK,
+        $(
+            /// Pin
+            pub $pxi: $PXi<$PMODE>,
+        )+
+        #[doc(hidden)]
+        _extensible: (),
+    }
+
     impl GpioExt for $GPIOX {
         type Parts = Parts;
 
+        type PreservedParts = PreservedParts;
+
+        fn split_preserving(self, apb2: &mut APB2) -> Self::PreservedParts {
+            $GPIOX::enable(apb2);
+            PreservedParts {
+                ctl0: CTL0 { _ownership: () },
+                ctl1: CTL1 { _ownership: () },
+                octl: OCTL { _ownership: () },
+                lock: LOCK {
+                    tmp_bits: unsafe { &(*$GPIOX::ptr()).lock }.read().bits(),
+                    _ownership: ()
+                },
+                $(
+                    $pxi: $PXi {
+                        _typestate_mode: PhantomData,
+                    },
+                )+
+                _extensible: (),
+            }
+        }
+
         fn split(self, apb2: &mut APB2) -> Self::Parts {
             riscv::interrupt::free(|_| {
-                apb2.en().modify(|_,w| w.$en().set_bit());
-                apb2.rst().write(|w| w.$rst().set_bit());
-                apb2.rst().write(|w| w.$rst().clear_bit());
+                $GPIOX::enable(apb2);
+                $GPIOX::reset(apb2);
             });
             Parts {
                 ctl0: CTL0 { _ownership: () },
@@ -290,6 +797,17 @@ pub mod $gpiox {
         }
     }
 
+    impl super::private::Sealed for CTL0 {}
+
+    impl CtlRegister for CTL0 {
+        fn set_ctl_md(&mut self, i: u8, ctl_and_md: u32) {
+            let index = 4 * i as u32;
+            self.ctl0().modify(|r, w| unsafe {
+                w.bits((r.bits() & !(0b1111 << index)) | (ctl_and_md << index))
+            });
+        }
+    }
+
     /// Opaque CTL1 register
     pub struct CTL1 {
         _ownership: (),
@@ -301,6 +819,17 @@ pub mod $gpiox {
         }
     }
 
+    impl super::private::Sealed for CTL1 {}
+
+    impl CtlRegister for CTL1 {
+        fn set_ctl_md(&mut self, i: u8, ctl_and_md: u32) {
+            let index = 4 * (i as u32 - 8);
+            self.ctl1().modify(|r, w| unsafe {
+                w.bits((r.bits() & !(0b1111 << index)) | (ctl_and_md << index))
+            });
+        }
+    }
+
     /// Opaque OCTL register
     pub struct OCTL {
         _ownership: (),
@@ -335,9 +864,18 @@ pub mod $gpiox {
         /// Instead of returning the LOCK back, this function panics on lock failure.
         /// That's because we consider all lock failures comes from mistakes in
         /// underlying libraries or chip design which may be not proper for users
-        /// to handle by themselves. If this design results in mistake, please
-        /// fire an issue to let us know.
-        pub fn freeze(mut self) {
+        /// to handle by themselves. Use `try_freeze` to handle the failure instead.
+        pub fn freeze(self) {
+            if self.try_freeze().is_err() {
+                panic!("the LOCK freeze process won't succeed")
+            }
+        }
+
+        /// Freeze pin modes of this GPIO port, returns an error if the LKK bit
+        /// does not read back as locked.
+        ///
+        /// See `freeze` for details.
+        pub fn try_freeze(mut self) -> Result<(), LockError> {
             let tmp = self.tmp_bits;
             let a = tmp | 0x00010000;
             // write in special ways to lock the register
@@ -345,15 +883,37 @@ pub mod $gpiox {
                 self.lock().write(|w| unsafe { w.bits(a) });
                 self.lock().write(|w| unsafe { w.bits(tmp) });
                 self.lock().write(|w| unsafe { w.bits(a) });
+                // LKK reads 0 for the first time, then 1 if the lock is active
                 let ans1 = self.lock().read().bits();
                 let ans2 = self.lock().read().bits();
-                ans1 == 0 && ans2 & 0x00010000 != 0
+                ans1 & 0x00010000 == 0 && ans2 & 0x00010000 != 0
             });
-            // if success, this function returns
-            if !success {
-                panic!("the LOCK freeze process won't succeed")
+            if success {
+                Ok(())
+            } else {
+                Err(LockError)
             }
         }
+
+        /// Freeze pin modes of the pins in `group` together with pins marked by
+        /// `lock`, in one LKK sequence.
+        ///
+        /// Returns the group wrapped into `Locked`, or an error if the LKK bit does
+        /// not read back as locked. See `freeze` for details.
+        pub fn freeze_group<MODE>(
+            mut self,
+            group: PortGroup<MODE>,
+        ) -> Result<Locked<PortGroup<MODE>>, LockError> {
+            self.tmp_bits |= group.mask as u32;
+            self.try_freeze()?;
+            Ok(Locked(group))
+        }
+
+        /// Returns whether the pin modes of this port are already frozen (LKK),
+        /// e.g. by a bootloader.
+        pub fn is_frozen(&mut self) -> bool {
+            self.lock().read().bits() & 0x00010000 != 0
+        }
     }
 
     /// Partially erased pin
@@ -362,6 +922,26 @@ pub mod $gpiox {
         _typestate_mode: PhantomData<MODE>,
     }
 
+    impl<MODE> From<$PXx<MODE>> for Pin<MODE> {
+        fn from(pin: $PXx<MODE>) -> Self {
+            pin.downgrade()
+        }
+    }
+
+    impl<MODE> $PXx<MODE> {
+        /// Erases the port from the type.
+        ///
+        /// This is useful when you want to collect pins from different ports
+        /// into an array where you need all the elements to have the same type.
+        pub fn downgrade(self) -> Pin<MODE> {
+            Pin {
+                port: $port,
+                i: self.i,
+                _typestate_mode: PhantomData,
+            }
+        }
+    }
+
     impl<MODE> InputPin for $PXx<Input<MODE>> {
         type Error = Infallible;
 
@@ -412,6 +992,99 @@ pub mod $gpiox {
         }
     }
 
+    /// Group of pins in this port
+    ///
+    /// Pins in a group are read or written together with one access to the
+    /// `ISTAT` or `BOP` register, e.g. for a parallel bus. Bit `i` of masks and
+    /// values refers to pin `i` of this port.
+    pub struct PortGroup<MODE> {
+        mask: u16,
+        _typestate_mode: PhantomData<MODE>,
+    }
+
+    impl<MODE> PortGroup<MODE> {
+        /// Creates a group from partially erased pins of this port.
+        pub fn new<I>(pins: I) -> Self
+        where
+            I: IntoIterator<Item = $PXx<MODE>>,
+        {
+            let mask = pins.into_iter().fold(0, |mask, pin| mask | (1 << pin.i));
+            PortGroup {
+                mask,
+                _typestate_mode: PhantomData,
+            }
+        }
+
+        /// Returns the mask of pins in this group.
+        pub fn mask(&self) -> u16 {
+            self.mask
+        }
+
+        /// Reads input levels of pins in this group from `ISTAT`; bits of other
+        /// pins are zero.
+        pub fn read_bits(&self) -> u16 {
+            (unsafe { &(*$GPIOX::ptr()).istat }.read().bits() as u16) & self.mask
+        }
+
+        /// Splits the group back into partially erased pins.
+        pub fn release(self) -> impl Iterator<Item = $PXx<MODE>> {
+            let mask = self.mask;
+            (0..16).filter(move |i| mask & (1 << i) != 0).map(|i| $PXx {
+                i,
+                _typestate_mode: PhantomData,
+            })
+        }
+    }
+
+    impl<MODE> PortGroup<Output<MODE>> {
+        /// Sets pins in `mask` to levels in `value` with one `BOP` write; pins out
+        /// of this group are ignored.
+        pub fn write_bits(&mut self, mask: u16, value: u16) {
+            let mask = (mask & self.mask) as u32;
+            let value = value as u32;
+            // BOP[15:0] sets the pins and BOP[31:16] clears them
+            let bits = (value & mask) | ((!value & mask) << 16);
+            unsafe { &(*$GPIOX::ptr()).bop }.write(|w| unsafe { w.bits(bits) });
+        }
+    }
+
+    impl<MODE> Locked<PortGroup<MODE>> {
+        /// Returns the mask of pins in this group.
+        pub fn mask(&self) -> u16 {
+            self.0.mask()
+        }
+
+        /// Reads input levels of pins in this group from `ISTAT`; bits of other
+        /// pins are zero.
+        pub fn read_bits(&self) -> u16 {
+            self.0.read_bits()
+        }
+    }
+
+    impl<MODE> Locked<PortGroup<Output<MODE>>> {
+        /// Sets pins in `mask` to levels in `value` with one `BOP` write; pins out
+        /// of this group are ignored.
+        pub fn write_bits(&mut self, mask: u16, value: u16) {
+            self.0.write_bits(mask, value)
+        }
+    }
+
+    impl<MODE> crate::exti::private::Sealed for $PXx<Input<MODE>> {}
+
+    impl<MODE> ExtiPin for $PXx<Input<MODE>> {
+        fn exti_source(&self) -> (u8, u8) {
+            ($port, self.i)
+        }
+    }
+
+    impl crate::afio::private::Sealed for $PXx<Alternate<PushPull>> {}
+
+    impl EventOutPin for $PXx<Alternate<PushPull>> {
+        fn event_source(&self) -> (u8, u8) {
+            ($port, self.i)
+        }
+    }
+
     impl InputPin for $PXx<Output<OpenDrain>> {
         type Error = Infallible;
 
@@ -549,6 +1222,21 @@ $(
             self.into_with_ctrl_md($ctl, ctrl_md)
         }
 
+        /// Configures the pin into a `DynamicPin`, whose mode could be switched at
+        /// runtime; it starts as a floating input pin.
+        pub fn into_dynamic(self, $ctl: &mut $CTL) -> DynamicPin<$CTL> {
+            let _: $PXi<Input<Floating>> = self.into_with_ctrl_md($ctl, 0b01_00);
+            DynamicPin {
+                pin: Pin {
+                    port: $port,
+                    i: $i,
+                    _typestate_mode: PhantomData,
+                },
+                mode: DynamicMode::InputFloating,
+                _ctl: PhantomData,
+            }
+        }
+
         #[inline]
         fn into_with_ctrl_md<T>(self, $ctl: &mut $CTL, ctl_and_md: u32) -> $PXi<T> {
             $ctl.$ctl().modify(|r, w| unsafe {
@@ -583,6 +1271,84 @@ $(
         }
     }
 
+    impl $PXi<Unknown> {
+        /// Checks the current pin mode in registers, returns the pin in `MODE`
+        /// if they match, or the pin itself otherwise.
+        pub fn try_into_mode<MODE: PinMode>(self) -> Result<$PXi<MODE>, Self> {
+            let gpio = unsafe { &*$GPIOX::ptr() };
+            let ctl = if $i < 8 { gpio.ctl0.read().bits() } else { gpio.ctl1.read().bits() };
+            let ctl_md = (ctl >> Self::CTL_MD_INDEX) & 0b1111;
+            let octl = gpio.octl.read().bits() & (1 << Self::OP_LK_INDEX) != 0;
+            if MODE::matches(ctl_md, octl) {
+                Ok($PXi {
+                    _typestate_mode: PhantomData,
+                })
+            } else {
+                Err(self)
+            }
+        }
+
+        /// Assumes the pin is in `MODE` without checking the registers.
+        ///
+        /// # Safety
+        ///
+        /// The pin must be actually configured in `MODE`, or drivers using this
+        /// pin may misbehave or damage external circuits.
+        pub unsafe fn assume_mode<MODE>(self) -> $PXi<MODE> {
+            $PXi {
+                _typestate_mode: PhantomData,
+            }
+        }
+    }
+
+    impl<MODE> $PXi<Output<MODE>> {
+        /// Changes the maximum output speed of this pin.
+        pub fn set_speed<SPEED: Speed>(&mut self, $ctl: &mut $CTL) {
+            self.set_md_bits($ctl, SPEED::MD_BITS)
+        }
+    }
+
+    impl<MODE> $PXi<Alternate<MODE>> {
+        /// Changes the maximum output speed of this pin.
+        pub fn set_speed<SPEED: Speed>(&mut self, $ctl: &mut $CTL) {
+            self.set_md_bits($ctl, SPEED::MD_BITS)
+        }
+    }
+
+    impl<MODE> $PXi<MODE> {
+        // Only changes the MD[1:0] bits, leaves CTL[1:0] bits unchanged
+        #[inline]
+        fn set_md_bits(&mut self, $ctl: &mut $CTL, md: u32) {
+            $ctl.$ctl().modify(|r, w| unsafe {
+                w.bits(
+                    (r.bits() & !(0b11 << Self::CTL_MD_INDEX))
+                        | (md << Self::CTL_MD_INDEX),
+                )
+            });
+        }
+    }
+
+    impl<MODE> JtagPin for $PXi<MODE> {
+        type Claimed = $PXi<Jtag>;
+
+        type Released = $PXi<Input<Floating>>;
+
+        type Ctl = $CTL;
+
+        fn claim(self) -> Self::Claimed {
+            $PXi {
+                _typestate_mode: PhantomData,
+            }
+        }
+
+        fn release(self, $ctl: &mut $CTL) -> Self::Released {
+            $ctl.set_ctl_md($i, 0b01_00);
+            $PXi {
+                _typestate_mode: PhantomData,
+            }
+        }
+    }
+
     impl<MODE> Unlock for Locked<$PXi<MODE>>
     where
         MODE: Active,
@@ -616,6 +1382,22 @@ $(
                 _typestate_mode: PhantomData
             }
         }
+
+        /// Erases both the port and the pin number from the type.
+        ///
+        /// This equals to `downgrade` twice, see `Pin` for details.
+        pub fn erase(self) -> Pin<MODE> {
+            self.downgrade().downgrade()
+        }
+    }
+
+    impl<MODE> From<$PXi<MODE>> for Pin<MODE>
+    where
+        MODE: Active
+    {
+        fn from(pin: $PXi<MODE>) -> Self {
+            pin.erase()
+        }
     }
 
     impl<MODE> InputPin for $PXi<Input<MODE>> {
@@ -632,6 +1414,22 @@ $(
         }
     }
 
+    impl<MODE> crate::exti::private::Sealed for $PXi<Input<MODE>> {}
+
+    impl<MODE> ExtiPin for $PXi<Input<MODE>> {
+        fn exti_source(&self) -> (u8, u8) {
+            ($port, $i)
+        }
+    }
+
+    impl crate::afio::private::Sealed for $PXi<Alternate<PushPull>> {}
+
+    impl EventOutPin for $PXi<Alternate<PushPull>> {
+        fn event_source(&self) -> (u8, u8) {
+            ($port, $i)
+        }
+    }
+
     impl<MODE> OutputPin for $PXi<Output<MODE>> {
         type Error = Infallible;
 
@@ -722,97 +1520,97 @@ $(
     };
 }
 
-impl_gpio! { GPIOA, gpioa, gpioa, paen, parst, PAx, [
-    PA0: (pa0, 0, Input<Floating>, CTL0, ctl0),
-    PA1: (pa1, 1, Input<Floating>, CTL0, ctl0),
-    PA2: (pa2, 2, Input<Floating>, CTL0, ctl0),
-    PA3: (pa3, 3, Input<Floating>, CTL0, ctl0),
-    PA4: (pa4, 4, Input<Floating>, CTL0, ctl0),
-    PA5: (pa5, 5, Input<Floating>, CTL0, ctl0),
-    PA6: (pa6, 6, Input<Floating>, CTL0, ctl0),
-    PA7: (pa7, 7, Input<Floating>, CTL0, ctl0),
-    PA8: (pa8, 8, Input<Floating>, CTL1, ctl1),
-    PA9: (pa9, 9, Input<Floating>, CTL1, ctl1),
-    PA10: (pa10, 10, Input<Floating>, CTL1, ctl1),
-    PA11: (pa11, 11, Input<Floating>, CTL1, ctl1),
-    PA12: (pa12, 12, Input<Floating>, CTL1, ctl1),
-    PA13: (pa13, 13, Input<PullUp>, CTL1, ctl1),
-    PA14: (pa14, 14, Input<PullDown>, CTL1, ctl1),
-    PA15: (pa15, 15, Input<PullUp>, CTL1, ctl1),
+impl_gpio! { GPIOA, gpioa, gpioa, 0, PAx, [
+    PA0: (pa0, 0, Input<Floating>, Unknown, CTL0, ctl0),
+    PA1: (pa1, 1, Input<Floating>, Unknown, CTL0, ctl0),
+    PA2: (pa2, 2, Input<Floating>, Unknown, CTL0, ctl0),
+    PA3: (pa3, 3, Input<Floating>, Unknown, CTL0, ctl0),
+    PA4: (pa4, 4, Input<Floating>, Unknown, CTL0, ctl0),
+    PA5: (pa5, 5, Input<Floating>, Unknown, CTL0, ctl0),
+    PA6: (pa6, 6, Input<Floating>, Unknown, CTL0, ctl0),
+    PA7: (pa7, 7, Input<Floating>, Unknown, CTL0, ctl0),
+    PA8: (pa8, 8, Input<Floating>, Unknown, CTL1, ctl1),
+    PA9: (pa9, 9, Input<Floating>, Unknown, CTL1, ctl1),
+    PA10: (pa10, 10, Input<Floating>, Unknown, CTL1, ctl1),
+    PA11: (pa11, 11, Input<Floating>, Unknown, CTL1, ctl1),
+    PA12: (pa12, 12, Input<Floating>, Unknown, CTL1, ctl1),
+    PA13: (pa13, 13, Jtag, Jtag, CTL1, ctl1),
+    PA14: (pa14, 14, Jtag, Jtag, CTL1, ctl1),
+    PA15: (pa15, 15, Jtag, Jtag, CTL1, ctl1),
 ] }
 
-impl_gpio! { GPIOB, gpiob, gpioa, pben, pbrst, PBx, [
-    PB0: (pb0, 0, Input<Floating>, CTL0, ctl0),
-    PB1: (pb1, 1, Input<Floating>, CTL0, ctl0),
-    PB2: (pb2, 2, Input<Floating>, CTL0, ctl0),
-    PB3: (pb3, 3, Input<Floating>, CTL0, ctl0),
-    PB4: (pb4, 4, Input<PullUp>, CTL0, ctl0),
-    PB5: (pb5, 5, Input<Floating>, CTL0, ctl0),
-    PB6: (pb6, 6, Input<Floating>, CTL0, ctl0),
-    PB7: (pb7, 7, Input<Floating>, CTL0, ctl0),
-    PB8: (pb8, 8, Input<Floating>, CTL1, ctl1),
-    PB9: (pb9, 9, Input<Floating>, CTL1, ctl1),
-    PB10: (pb10, 10, Input<Floating>, CTL1, ctl1),
-    PB11: (pb11, 11, Input<Floating>, CTL1, ctl1),
-    PB12: (pb12, 12, Input<Floating>, CTL1, ctl1),
-    PB13: (pb13, 13, Input<Floating>, CTL1, ctl1),
-    PB14: (pb14, 14, Input<Floating>, CTL1, ctl1),
-    PB15: (pb15, 15, Input<Floating>, CTL1, ctl1),
+impl_gpio! { GPIOB, gpiob, gpioa, 1, PBx, [
+    PB0: (pb0, 0, Input<Floating>, Unknown, CTL0, ctl0),
+    PB1: (pb1, 1, Input<Floating>, Unknown, CTL0, ctl0),
+    PB2: (pb2, 2, Input<Floating>, Unknown, CTL0, ctl0),
+    PB3: (pb3, 3, Jtag, Jtag, CTL0, ctl0),
+    PB4: (pb4, 4, Jtag, Jtag, CTL0, ctl0),
+    PB5: (pb5, 5, Input<Floating>, Unknown, CTL0, ctl0),
+    PB6: (pb6, 6, Input<Floating>, Unknown, CTL0, ctl0),
+    PB7: (pb7, 7, Input<Floating>, Unknown, CTL0, ctl0),
+    PB8: (pb8, 8, Input<Floating>, Unknown, CTL1, ctl1),
+    PB9: (pb9, 9, Input<Floating>, Unknown, CTL1, ctl1),
+    PB10: (pb10, 10, Input<Floating>, Unknown, CTL1, ctl1),
+    PB11: (pb11, 11, Input<Floating>, Unknown, CTL1, ctl1),
+    PB12: (pb12, 12, Input<Floating>, Unknown, CTL1, ctl1),
+    PB13: (pb13, 13, Input<Floating>, Unknown, CTL1, ctl1),
+    PB14: (pb14, 14, Input<Floating>, Unknown, CTL1, ctl1),
+    PB15: (pb15, 15, Input<Floating>, Unknown, CTL1, ctl1),
 ] }
 
-impl_gpio! { GPIOC, gpioc, gpioa, pcen, pcrst, PCx, [
-    PC0: (pc0, 0, Input<Floating>, CTL0, ctl0),
-    PC1: (pc1, 1, Input<Floating>, CTL0, ctl0),
-    PC2: (pc2, 2, Input<Floating>, CTL0, ctl0),
-    PC3: (pc3, 3, Input<Floating>, CTL0, ctl0),
-    PC4: (pc4, 4, Input<Floating>, CTL0, ctl0),
-    PC5: (pc5, 5, Input<Floating>, CTL0, ctl0),
-    PC6: (pc6, 6, Input<Floating>, CTL0, ctl0),
-    PC7: (pc7, 7, Input<Floating>, CTL0, ctl0),
-    PC8: (pc8, 8, Input<Floating>, CTL1, ctl1),
-    PC9: (pc9, 9, Input<Floating>, CTL1, ctl1),
-    PC10: (pc10, 10, Input<Floating>, CTL1, ctl1),
-    PC11: (pc11, 11, Input<Floating>, CTL1, ctl1),
-    PC12: (pc12, 12, Input<Floating>, CTL1, ctl1),
-    PC13: (pc13, 13, Input<Floating>, CTL1, ctl1),
-    PC14: (pc14, 14, Input<Floating>, CTL1, ctl1),
-    PC15: (pc15, 15, Input<Floating>, CTL1, ctl1),
+impl_gpio! { GPIOC, gpioc, gpioa, 2, PCx, [
+    PC0: (pc0, 0, Input<Floating>, Unknown, CTL0, ctl0),
+    PC1: (pc1, 1, Input<Floating>, Unknown, CTL0, ctl0),
+    PC2: (pc2, 2, Input<Floating>, Unknown, CTL0, ctl0),
+    PC3: (pc3, 3, Input<Floating>, Unknown, CTL0, ctl0),
+    PC4: (pc4, 4, Input<Floating>, Unknown, CTL0, ctl0),
+    PC5: (pc5, 5, Input<Floating>, Unknown, CTL0, ctl0),
+    PC6: (pc6, 6, Input<Floating>, Unknown, CTL0, ctl0),
+    PC7: (pc7, 7, Input<Floating>, Unknown, CTL0, ctl0),
+    PC8: (pc8, 8, Input<Floating>, Unknown, CTL1, ctl1),
+    PC9: (pc9, 9, Input<Floating>, Unknown, CTL1, ctl1),
+    PC10: (pc10, 10, Input<Floating>, Unknown, CTL1, ctl1),
+    PC11: (pc11, 11, Input<Floating>, Unknown, CTL1, ctl1),
+    PC12: (pc12, 12, Input<Floating>, Unknown, CTL1, ctl1),
+    PC13: (pc13, 13, Input<Floating>, Unknown, CTL1, ctl1),
+    PC14: (pc14, 14, Input<Floating>, Unknown, CTL1, ctl1),
+    PC15: (pc15, 15, Input<Floating>, Unknown, CTL1, ctl1),
 ] }
 
-impl_gpio! { GPIOD, gpiod, gpioa, pden, pdrst, PDx, [
-    PD0: (pd0, 0, Input<Floating>, CTL0, ctl0),
-    PD1: (pd1, 1, Input<Floating>, CTL0, ctl0),
-    PD2: (pd2, 2, Input<Floating>, CTL0, ctl0),
-    PD3: (pd3, 3, Input<Floating>, CTL0, ctl0),
-    PD4: (pd4, 4, Input<Floating>, CTL0, ctl0),
-    PD5: (pd5, 5, Input<Floating>, CTL0, ctl0),
-    PD6: (pd6, 6, Input<Floating>, CTL0, ctl0),
-    PD7: (pd7, 7, Input<Floating>, CTL0, ctl0),
-    PD8: (pd8, 8, Input<Floating>, CTL1, ctl1),
-    PD9: (pd9, 9, Input<Floating>, CTL1, ctl1),
-    PD10: (pd10, 10, Input<Floating>, CTL1, ctl1),
-    PD11: (pd11, 11, Input<Floating>, CTL1, ctl1),
-    PD12: (pd12, 12, Input<Floating>, CTL1, ctl1),
-    PD13: (pd13, 13, Input<Floating>, CTL1, ctl1),
-    PD14: (pd14, 14, Input<Floating>, CTL1, ctl1),
-    PD15: (pd15, 15, Input<Floating>, CTL1, ctl1),
+impl_gpio! { GPIOD, gpiod, gpioa, 3, PDx, [
+    PD0: (pd0, 0, Input<Floating>, Unknown, CTL0, ctl0),
+    PD1: (pd1, 1, Input<Floating>, Unknown, CTL0, ctl0),
+    PD2: (pd2, 2, Input<Floating>, Unknown, CTL0, ctl0),
+    PD3: (pd3, 3, Input<Floating>, Unknown, CTL0, ctl0),
+    PD4: (pd4, 4, Input<Floating>, Unknown, CTL0, ctl0),
+    PD5: (pd5, 5, Input<Floating>, Unknown, CTL0, ctl0),
+    PD6: (pd6, 6, Input<Floating>, Unknown, CTL0, ctl0),
+    PD7: (pd7, 7, Input<Floating>, Unknown, CTL0, ctl0),
+    PD8: (pd8, 8, Input<Floating>, Unknown, CTL1, ctl1),
+    PD9: (pd9, 9, Input<Floating>, Unknown, CTL1, ctl1),
+    PD10: (pd10, 10, Input<Floating>, Unknown, CTL1, ctl1),
+    PD11: (pd11, 11, Input<Floating>, Unknown, CTL1, ctl1),
+    PD12: (pd12, 12, Input<Floating>, Unknown, CTL1, ctl1),
+    PD13: (pd13, 13, Input<Floating>, Unknown, CTL1, ctl1),
+    PD14: (pd14, 14, Input<Floating>, Unknown, CTL1, ctl1),
+    PD15: (pd15, 15, Input<Floating>, Unknown, CTL1, ctl1),
 ] }
 
-impl_gpio! { GPIOE, gpioe, gpioa, peen, perst, PEx, [
-    PE0: (pe0, 0, Input<Floating>, CTL0, ctl0),
-    PE1: (pe1, 1, Input<Floating>, CTL0, ctl0),
-    PE2: (pe2, 2, Input<Floating>, CTL0, ctl0),
-    PE3: (pe3, 3, Input<Floating>, CTL0, ctl0),
-    PE4: (pe4, 4, Input<Floating>, CTL0, ctl0),
-    PE5: (pe5, 5, Input<Floating>, CTL0, ctl0),
-    PE6: (pe6, 6, Input<Floating>, CTL0, ctl0),
-    PE7: (pe7, 7, Input<Floating>, CTL0, ctl0),
-    PE8: (pe8, 8, Input<Floating>, CTL1, ctl1),
-    PE9: (pe9, 9, Input<Floating>, CTL1, ctl1),
-    PE10: (pe10, 10, Input<Floating>, CTL1, ctl1),
-    PE11: (pe11, 11, Input<Floating>, CTL1, ctl1),
-    PE12: (pe12, 12, Input<Floating>, CTL1, ctl1),
-    PE13: (pe13, 13, Input<Floating>, CTL1, ctl1),
-    PE14: (pe14, 14, Input<Floating>, CTL1, ctl1),
-    PE15: (pe15, 15, Input<Floating>, CTL1, ctl1),
+impl_gpio! { GPIOE, gpioe, gpioa, 4, PEx, [
+    PE0: (pe0, 0, Input<Floating>, Unknown, CTL0, ctl0),
+    PE1: (pe1, 1, Input<Floating>, Unknown, CTL0, ctl0),
+    PE2: (pe2, 2, Input<Floating>, Unknown, CTL0, ctl0),
+    PE3: (pe3, 3, Input<Floating>, Unknown, CTL0, ctl0),
+    PE4: (pe4, 4, Input<Floating>, Unknown, CTL0, ctl0),
+    PE5: (pe5, 5, Input<Floating>, Unknown, CTL0, ctl0),
+    PE6: (pe6, 6, Input<Floating>, Unknown, CTL0, ctl0),
+    PE7: (pe7, 7, Input<Floating>, Unknown, CTL0, ctl0),
+    PE8: (pe8, 8, Input<Floating>, Unknown, CTL1, ctl1),
+    PE9: (pe9, 9, Input<Floating>, Unknown, CTL1, ctl1),
+    PE10: (pe10, 10, Input<Floating>, Unknown, CTL1, ctl1),
+    PE11: (pe11, 11, Input<Floating>, Unknown, CTL1, ctl1),
+    PE12: (pe12, 12, Input<Floating>, Unknown, CTL1, ctl1),
+    PE13: (pe13, 13, Input<Floating>, Unknown, CTL1, ctl1),
+    PE14: (pe14, 14, Input<Floating>, Unknown, CTL1, ctl1),
+    PE15: (pe15, 15, Input<Floating>, Unknown, CTL1, ctl1),
 ] }
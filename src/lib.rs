@@ -6,13 +6,13 @@
 //!
 //! # Usage
 //! Add this crate to your dependencies:
-//! ```
+//! ```toml
 //! [dependencies]
 //! gd32vf103-hal = "0.0"
 //! ```
 //!
 //! # Example
-//! ```
+//! ```ignore
 //! #![no_std]
 //! #![no_main]
 //! // choose a panic handler crate
@@ -41,7 +41,7 @@
 //! }
 //! ```
 
-#![no_std]
+#![cfg_attr(not(test), no_std)]
 // #![deny(missing_docs)]
 
 pub use gd32vf103_pac as pac;
@@ -54,6 +54,7 @@ pub mod ctimer;
 pub mod debug;
 pub mod delay;
 pub mod esig;
+pub mod exti;
 pub mod fmc;
 pub mod gpio;
 pub mod rcu;
@@ -65,6 +66,7 @@ pub mod wdog;
 
 /// Prelude
 pub mod prelude {
+    pub use crate::exti::ExtiPin as _gd32vf103_hal_exti_ExtiPin;
     pub use crate::gpio::GpioExt as _gd32vf103_hal_gpio_GpioExt;
     pub use crate::gpio::{Unlock as _gd32vf103_hal_gpio_Unlock, UpTo10MHz, UpTo2MHz, UpTo50MHz};
     pub use crate::rcu::RcuExt as _gd32vf103_hal_rcu_RcuExt;
@@ -1,5 +1,7 @@
 //! Reset and Control Unit
 
+use crate::gpio::gpioa::PA8;
+use crate::gpio::{Alternate, PushPull};
 use crate::pac::{rcu, RCU};
 use crate::time::*;
 use core::num::NonZeroU32;
@@ -18,7 +20,7 @@ impl RcuExt for RCU {
             ahb: AHB { _ownership: () },
             cfg: CFG { _ownership: () },
             bdctl: BDCTL { _ownership: () },
-            // rstsck: RSTSCK
+            rstsck: RSTSCK { _ownership: () },
             // dsv: DSV
             _todo: (),
         }
@@ -44,7 +46,7 @@ pub struct Rcu {
     pub ahb: AHB,
     /// Clock configuration registers
     ///
-    /// Constrains `CFG0` and `CFG1` and `CTL0`
+    /// Constrains `CFG0` and `CFG1` and `CTL0`, and clock monitor bits in `INT`
     pub cfg: CFG,
     // // todo: remove
     // pub clocks: Clocks,
@@ -52,6 +54,10 @@ pub struct Rcu {
     ///
     /// Constrains `BDCTL`.
     pub bdctl: BDCTL,
+    /// Reset source and clock register
+    ///
+    /// Constrains `RSTSCK`.
+    pub rstsck: RSTSCK,
     // ...
     _todo: (),
 }
@@ -70,7 +76,11 @@ impl AHB {
     pub(crate) fn en(&mut self) -> &rcu::AHBEN {
         unsafe { &(*RCU::ptr()).ahben }
     }
-    // pub(crate) fn rst
+
+    #[inline]
+    pub(crate) fn rst(&mut self) -> &rcu::AHBRST {
+        unsafe { &(*RCU::ptr()).ahbrst }
+    }
 }
 
 /// Advanced Pheripheral Bus 1 (APB1) registers
@@ -111,9 +121,102 @@ impl APB2 {
     }
 }
 
+/// Bus which a peripheral is connected to
+pub trait RcuBus {
+    /// The bus token, one of `AHB`, `APB1` and `APB2`
+    type Bus;
+}
+
+/// Peripherals whose clock could be enabled and disabled
+pub trait Enable: RcuBus {
+    /// Enable the peripheral clock.
+    fn enable(bus: &mut Self::Bus);
+
+    /// Disable the peripheral clock.
+    fn disable(bus: &mut Self::Bus);
+}
+
+/// Peripherals which could be reset by the RCU
+pub trait Reset: RcuBus {
+    /// Reset the peripheral by pulsing its reset bit.
+    fn reset(bus: &mut Self::Bus);
+}
+
+macro_rules! bus {
+    ($($PER:ident => ($BUS:ident, $en:ident $(, $rst:ident)?),)+) => {
+        $(
+            impl RcuBus for crate::pac::$PER {
+                type Bus = $BUS;
+            }
+
+            impl Enable for crate::pac::$PER {
+                #[inline]
+                fn enable(bus: &mut Self::Bus) {
+                    bus.en().modify(|_, w| w.$en().set_bit());
+                }
+
+                #[inline]
+                fn disable(bus: &mut Self::Bus) {
+                    bus.en().modify(|_, w| w.$en().clear_bit());
+                }
+            }
+
+            $(
+                impl Reset for crate::pac::$PER {
+                    #[inline]
+                    fn reset(bus: &mut Self::Bus) {
+                        bus.rst().modify(|_, w| w.$rst().set_bit());
+                        bus.rst().modify(|_, w| w.$rst().clear_bit());
+                    }
+                }
+            )?
+        )+
+    };
+}
+
+// Only USBFS could be reset on AHB (Section 5.3.11)
+bus! {
+    DMA0 => (AHB, dma0en),
+    DMA1 => (AHB, dma1en),
+    CRC => (AHB, crcen),
+    EXMC => (AHB, exmcen),
+    USBFS_GLOBAL => (AHB, usbfsen, usbfsrst),
+    TIMER1 => (APB1, timer1en, timer1rst),
+    TIMER2 => (APB1, timer2en, timer2rst),
+    TIMER3 => (APB1, timer3en, timer3rst),
+    TIMER4 => (APB1, timer4en, timer4rst),
+    TIMER5 => (APB1, timer5en, timer5rst),
+    TIMER6 => (APB1, timer6en, timer6rst),
+    WWDGT => (APB1, wwdgten, wwdgtrst),
+    SPI1 => (APB1, spi1en, spi1rst),
+    SPI2 => (APB1, spi2en, spi2rst),
+    USART1 => (APB1, usart1en, usart1rst),
+    USART2 => (APB1, usart2en, usart2rst),
+    UART3 => (APB1, uart3en, uart3rst),
+    UART4 => (APB1, uart4en, uart4rst),
+    I2C0 => (APB1, i2c0en, i2c0rst),
+    I2C1 => (APB1, i2c1en, i2c1rst),
+    CAN0 => (APB1, can0en, can0rst),
+    CAN1 => (APB1, can1en, can1rst),
+    BKP => (APB1, bkpien, bkpirst),
+    PMU => (APB1, pmuen, pmurst),
+    DAC => (APB1, dacen, dacrst),
+    AFIO => (APB2, afen, afrst),
+    GPIOA => (APB2, paen, parst),
+    GPIOB => (APB2, pben, pbrst),
+    GPIOC => (APB2, pcen, pcrst),
+    GPIOD => (APB2, pden, pdrst),
+    GPIOE => (APB2, peen, perst),
+    ADC0 => (APB2, adc0en, adc0rst),
+    ADC1 => (APB2, adc1en, adc1rst),
+    TIMER0 => (APB2, timer0en, timer0rst),
+    SPI0 => (APB2, spi0en, spi0rst),
+    USART0 => (APB2, usart0en, usart0rst),
+}
+
 /// Clock configuration registers
 ///
-/// Constrains `CFG0` and `CFG1` and `CTL0`
+/// Constrains `CFG0` and `CFG1` and `CTL0`, and clock monitor bits in `INT`
 pub struct CFG {
     _ownership: (),
 }
@@ -131,6 +234,66 @@ impl CFG {
     pub(crate) fn ctl(&mut self) -> &rcu::CTL {
         unsafe { &(*RCU::ptr()).ctl }
     }
+    #[inline]
+    pub(crate) fn int(&mut self) -> &rcu::INT {
+        unsafe { &(*RCU::ptr()).int }
+    }
+
+    /// Enable the HXTAL clock monitor (CKMEN).
+    ///
+    /// The monitor only works after HXTAL is stable. When it detects an HXTAL
+    /// failure, the hardware turns off HXTAL, switches CK_SYS to IRC8M, sets the
+    /// CKMIF flag and generates a non-maskable interrupt. Use `recover_hxtal_failure`
+    /// to get the frequencies after the failure.
+    pub fn enable_clock_monitor(&mut self) {
+        self.ctl().modify(|_, w| w.ckmen().set_bit());
+    }
+
+    /// Disable the HXTAL clock monitor.
+    pub fn disable_clock_monitor(&mut self) {
+        self.ctl().modify(|_, w| w.ckmen().clear_bit());
+    }
+
+    /// Returns whether the clock monitor has detected an HXTAL failure (CKMIF).
+    pub fn is_hxtal_failed(&mut self) -> bool {
+        self.int().read().ckmif().bit_is_set()
+    }
+
+    /// Clear the HXTAL failure flag by setting the CKMIC bit.
+    pub fn clear_hxtal_failure(&mut self) {
+        self.int().modify(|_, w| w.ckmic().set_bit());
+    }
+
+    /// Continue running on IRC8M after an HXTAL failure.
+    ///
+    /// This function clears the failure flag, makes sure that CK_SYS is clocked
+    /// by IRC8M, turns off HXTAL and all PLLs, and switches I2S clocks to CK_SYS.
+    /// AHB, APB and ADC prescalers are kept, so the returned clocks are `clocks`
+    /// scaled down to the 8MHz system clock; CK_USBFS is no longer valid.
+    pub fn recover_hxtal_failure(&mut self, clocks: Clocks) -> Clocks {
+        const IRC8M: u32 = 8_000_000;
+        self.clear_hxtal_failure();
+        // hardware already enables IRC8M on failure
+        self.ctl().modify(|_, w| w.irc8men().set_bit());
+        while self.ctl().read().irc8mstb().bit_is_clear() {}
+        self.cfg0().modify(|_, w| unsafe { w.scs().bits(0b00) });
+        while self.cfg0().read().scss().bits() != 0b00 {}
+        self.cfg1()
+            .modify(|_, w| w.i2s1sel().clear_bit().i2s2sel().clear_bit());
+        self.ctl().modify(|_, w| {
+            w.pllen().clear_bit();
+            w.pll1en().clear_bit();
+            w.pll2en().clear_bit();
+            w.hxtalen().clear_bit()
+        });
+        Clocks {
+            ck_sys: Hertz(IRC8M),
+            usb_valid: false,
+            ck_i2s1: Hertz(IRC8M),
+            ck_i2s2: Hertz(IRC8M),
+            ..clocks
+        }
+    }
 }
 
 // read the registers and store in struct, rather than hardcode defaults
@@ -138,8 +301,8 @@ impl CFG {
 // done(luojia65 2020-2-29) // TODO: Verify the result
 /// Frozen clock freqencies
 ///
-/// The existence of this value indicates that the core clock
-/// configuration can no longer be changed
+/// A `Clocks` value describes the clock configuration at the time it was
+/// produced; it's outdated once the configuration is changed again.
 #[derive(Clone, Copy)]
 pub struct Clocks {
     ck_sys: Hertz,
@@ -148,9 +311,82 @@ pub struct Clocks {
     apb2_shr: u8, // [0, 4] -> [2, 16]
     adc_div: u8,  // {2, 4, 6, 8, 12, 16}
     usb_valid: bool,
+    ck_i2s1: Hertz,
+    ck_i2s2: Hertz,
 }
 
 impl Clocks {
+    /// Reads the clock configuration currently programmed into the RCU.
+    ///
+    /// This is useful when the clocks are configured by a bootloader or other
+    /// code before this program. `hxtal` is the frequency of the external
+    /// oscillator; it's ignored if neither CK_SYS, the PLLs nor PREDV0 use HXTAL.
+    pub fn from_hardware(_cfg: &CFG, hxtal: impl Into<Hertz>) -> Clocks {
+        const IRC8M: u64 = 8_000_000;
+        let hxtal = hxtal.into().0 as u64;
+        let rcu = unsafe { &*RCU::ptr() };
+        let cfg0 = rcu.cfg0.read();
+        let cfg1 = rcu.cfg1.read();
+        // HXTAL / PREDV1, the input of PLL1 and PLL2
+        let ck_ext1 = hxtal / (cfg1.predv1().bits() as u64 + 1);
+        let ck_pll1 = ck_ext1 * pll12mf_mul(cfg1.pll1mf().bits()) as u64;
+        let ck_pll2 = ck_ext1 * pll12mf_mul(cfg1.pll2mf().bits()) as u64;
+        // PLL output doubled, as the multiplier could be 6.5
+        let ck_pll_mul2 = {
+            let input = if cfg0.pllsel().bit_is_clear() {
+                IRC8M / 2
+            } else if cfg1.predv0sel().bit_is_clear() {
+                hxtal / (cfg1.predv0().bits() as u64 + 1)
+            } else {
+                ck_pll1 / (cfg1.predv0().bits() as u64 + 1)
+            };
+            let pllmf = cfg0.pllmf_3_0().bits() | ((cfg0.pllmf_4().bit() as u8) << 4);
+            input * pllmf_mul2(pllmf) as u64
+        };
+        let ck_sys = match cfg0.scss().bits() {
+            0b00 => IRC8M,
+            0b01 => hxtal,
+            _ => ck_pll_mul2 / 2,
+        } as u32;
+        // 0xxx: /1; 1000 ..= 1011: /2 ..= /16; 1100 ..= 1111: /64 ..= /512
+        let ahb_shr = match cfg0.ahbpsc().bits() {
+            0b0000..=0b0111 => 0,
+            bits @ 0b1000..=0b1011 => bits - 0b0111,
+            bits => bits - 0b0110,
+        };
+        // 0xx: /1; 100 ..= 111: /2 ..= /16
+        let apb_shr = |bits: u8| if bits < 0b100 { 0 } else { bits - 0b011 };
+        let adcpsc = cfg0.adcpsc_1_0().bits() | ((cfg0.adcpsc_2().bit() as u8) << 2);
+        let adc_div = match adcpsc {
+            0b000 | 0b100 => 2,
+            0b001 => 4,
+            0b010 => 6,
+            0b011 | 0b110 => 8,
+            0b101 => 12,
+            _ => 16,
+        };
+        // CK_USBFS = CK_PLL / {1.5, 1, 2.5, 2}, with the PLL clocked by HXTAL
+        let usb_valid = rcu.ctl.read().pllstb().bit_is_set()
+            && cfg0.pllsel().bit_is_set()
+            && match cfg0.usbfspsc().bits() {
+                0b00 => ck_pll_mul2 == 48_000_000 * 3,
+                0b01 => ck_pll_mul2 == 48_000_000 * 2,
+                0b10 => ck_pll_mul2 == 48_000_000 * 5,
+                _ => ck_pll_mul2 == 48_000_000 * 4,
+            };
+        let ck_i2s = |pll2: bool| Hertz(if pll2 { (ck_pll2 * 2) as u32 } else { ck_sys });
+        Clocks {
+            ck_sys: Hertz(ck_sys),
+            ahb_shr,
+            apb1_shr: apb_shr(cfg0.apb1psc().bits()),
+            apb2_shr: apb_shr(cfg0.apb2psc().bits()),
+            adc_div,
+            usb_valid,
+            ck_i2s1: ck_i2s(cfg1.i2s1sel().bit()),
+            ck_i2s2: ck_i2s(cfg1.i2s2sel().bit()),
+        }
+    }
+
     /// Returns the frequency of the system clock
     pub const fn ck_sys(&self) -> Hertz {
         self.ck_sys
@@ -171,8 +407,21 @@ impl Clocks {
         Hertz(self.ck_sys.0 >> (self.ahb_shr + self.apb2_shr))
     }
 
-    /// Returns the freqency of the CK_TIMERx clock
-    pub const fn ck_timerx(&self) -> Hertz {
+    /// Returns the freqency of the CK_TIMERx clock for timers on APB1
+    /// (TIMER1 to TIMER6)
+    ///
+    /// It's CK_APB1 if APB1 is not divided, or twice CK_APB1 otherwise.
+    pub const fn ck_timer_apb1(&self) -> Hertz {
+        Hertz(
+            self.ck_sys.0
+                >> (self.ahb_shr + self.apb1_shr - [0, 1, 1, 1, 1][self.apb1_shr as usize]),
+        )
+    }
+
+    /// Returns the freqency of the CK_TIMERx clock for timers on APB2 (TIMER0)
+    ///
+    /// It's CK_APB2 if APB2 is not divided, or twice CK_APB2 otherwise.
+    pub const fn ck_timer_apb2(&self) -> Hertz {
         // Hertz(self.ck_sys.0 >> (self.ahb_shr + self.apb2_shr
         //     - if self.apb2_shr == 0 { 0 } else { 1 }))
         Hertz(
@@ -181,6 +430,12 @@ impl Clocks {
         )
     }
 
+    /// Returns the freqency of the CK_TIMERx clock for timers on APB2
+    #[deprecated(note = "use `ck_timer_apb1` or `ck_timer_apb2` for the timer's bus")]
+    pub const fn ck_timerx(&self) -> Hertz {
+        self.ck_timer_apb2()
+    }
+
     /// Returns the freqency of the CK_ADCx clock
     pub const fn ck_adc(&self) -> Hertz {
         Hertz((self.ck_sys.0 >> (self.ahb_shr + self.apb2_shr)) / self.adc_div as u32)
@@ -190,6 +445,74 @@ impl Clocks {
     pub const fn ck_usbfs_valid(&self) -> bool {
         self.usb_valid
     }
+
+    /// Returns the frequency of the I2S1 clock, used by SPI1 in I2S mode
+    pub const fn ck_i2s1(&self) -> Hertz {
+        self.ck_i2s1
+    }
+
+    /// Returns the frequency of the I2S2 clock, used by SPI2 in I2S mode
+    pub const fn ck_i2s2(&self) -> Hertz {
+        self.ck_i2s2
+    }
+}
+
+/// Clock domain a `ClockError` refers to
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockDomain {
+    /// External high speed oscillator (HXTAL)
+    Hxtal,
+    /// System clock (CK_SYS)
+    Sys,
+    /// AHB clock (CK_AHB)
+    Ahb,
+    /// APB1 clock (CK_APB1)
+    Apb1,
+    /// APB2 clock (CK_APB2)
+    Apb2,
+    /// ADC clock (CK_ADCx)
+    Adc,
+    /// USBFS clock (CK_USBFS)
+    Usb,
+    /// I2S1 and I2S2 clocks (CK_I2S)
+    I2s,
+}
+
+/// Clock configuration error
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockError {
+    /// The frequency is out of the limits in Figure 5.2, the Manual
+    OverLimit(ClockDomain),
+    /// No prescaler value divides the source clock into the frequency
+    NoDivider(ClockDomain),
+    /// The required PLL multiplier is out of range
+    PllMultiplier(ClockDomain),
+    /// HXTAL did not stabilize in time
+    HxtalTimeout,
+    /// IRC8M did not stabilize in time
+    Irc8mTimeout,
+    /// PLL, PLL1 or PLL2 did not stabilize in time
+    PllTimeout,
+    /// LXTAL did not stabilize in time
+    LxtalTimeout,
+    /// IRC40K did not stabilize in time
+    Irc40kTimeout,
+    /// CK_SYS did not switch to the selected source in time
+    SwitchTimeout,
+}
+
+// Checks the frequency against its limits in Figure 5.2, the Manual
+#[inline]
+fn limited(
+    freq_hz: u32,
+    min: u32,
+    max: u32,
+    domain: ClockDomain,
+) -> Result<Option<NonZeroU32>, ClockError> {
+    if !(min..=max).contains(&freq_hz) {
+        return Err(ClockError::OverLimit(domain));
+    }
+    Ok(NonZeroU32::new(freq_hz))
 }
 
 /// Strict clock configurator
@@ -207,6 +530,9 @@ impl Clocks {
 /// 50MHz into `Strict` would result in a panic when performing `freeze`; however input
 /// same 50MHz into `Precise` it would not panic, but would set and freeze into
 /// 50.20MHz as the frequency error is smallest.
+///
+/// Use `try_freeze` and the `try_*` setters to handle unreachable frequencies as a
+/// `ClockError` instead of a panic.
 #[derive(Default)]
 pub struct Strict {
     hxtal: Option<NonZeroU32>,
@@ -216,11 +542,12 @@ pub struct Strict {
     target_ck_apb1: Option<NonZeroU32>,
     target_ck_apb2: Option<NonZeroU32>,
     target_ck_adc: Option<NonZeroU32>,
+    require_usbfs: bool,
 }
 
 impl Strict {
     /// Create a configurator
-    pub fn new() -> Self {
+    pub const fn new() -> Self {
         Strict {
             hxtal: None,
             target_ck_sys: None,
@@ -229,27 +556,45 @@ impl Strict {
             target_ck_apb1: None,
             target_ck_apb2: None,
             target_ck_adc: None,
+            require_usbfs: false,
         }
     }
 
     /// Prefer use HXTAL (external oscillator) as the clock source.
-    pub fn use_hxtal(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz >= 4_000_000 && freq_hz <= 32_000_000); // Figure 5.2, the Manual
-        self.hxtal = NonZeroU32::new(freq_hz);
-        self
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is out of 4MHz to 32MHz, this function panics.
+    pub fn use_hxtal(self, freq: impl Into<Hertz>) -> Self {
+        self.try_use_hxtal(freq).expect("invalid frequency")
+    }
+
+    /// Prefer use HXTAL (external oscillator) as the clock source, returns an
+    /// error if the frequency is out of 4MHz to 32MHz.
+    pub fn try_use_hxtal(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.hxtal = limited(freq.into().0, 4_000_000, 32_000_000, ClockDomain::Hxtal)?;
+        Ok(self)
     }
 
     /// Sets the desired frequency for the CK_SYS clock
-    pub fn ck_sys(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz <= 108_000_000); // Figure 5.2, the Manual
-        self.target_ck_sys = NonZeroU32::new(freq_hz);
-        self
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_sys(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_sys(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_SYS clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_sys(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_sys = limited(freq.into().0, 0, 108_000_000, ClockDomain::Sys)?;
+        Ok(self)
     }
 
-    #[doc(hidden)] // todo
-    /// Sets the desired frequency for the CK_I2S clock
+    /// Sets the desired frequency for the CK_I2S clock of both I2S1 and I2S2
+    ///
+    /// CK_I2S is clocked by CK_SYS, or by CK_PLL2 * 2 which needs HXTAL.
     pub fn ck_i2s(mut self, freq: impl Into<Hertz>) -> Self {
         let freq_hz = freq.into().0;
         self.target_ck_i2s = NonZeroU32::new(freq_hz);
@@ -257,34 +602,74 @@ impl Strict {
     }
 
     /// Sets the desired frequency for the CK_AHB clock
-    pub fn ck_ahb(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz <= 108_000_000); // Figure 5.2, the Manual
-        self.target_ck_ahb = NonZeroU32::new(freq_hz);
-        self
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_ahb(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_ahb(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_AHB clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_ahb(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_ahb = limited(freq.into().0, 0, 108_000_000, ClockDomain::Ahb)?;
+        Ok(self)
     }
 
     /// Sets the desired frequency for the CK_APB1 clock
-    pub fn ck_apb1(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz <= 54_000_000); // Figure 5.2, the Manual
-        self.target_ck_apb1 = NonZeroU32::new(freq_hz);
-        self
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 54MHz, this function panics.
+    pub fn ck_apb1(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_apb1(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_APB1 clock, returns an error if the
+    /// frequency is over 54MHz.
+    pub fn try_ck_apb1(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_apb1 = limited(freq.into().0, 0, 54_000_000, ClockDomain::Apb1)?;
+        Ok(self)
     }
 
     /// Sets the desired frequency for the CK_APB2 clock
-    pub fn ck_apb2(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz <= 108_000_000); // Figure 5.2, the Manual
-        self.target_ck_apb2 = NonZeroU32::new(freq_hz);
-        self
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_apb2(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_apb2(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_APB2 clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_apb2(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_apb2 = limited(freq.into().0, 0, 108_000_000, ClockDomain::Apb2)?;
+        Ok(self)
     }
 
     /// Sets the desired frequency for the CK_ADCx clock
-    pub fn ck_adc(mut self, freq: impl Into<Hertz>) -> Self {
-        let freq_hz = freq.into().0;
-        assert!(freq_hz <= 14_000_000); // Figure 5.2, the Manual
-        self.target_ck_adc = NonZeroU32::new(freq_hz);
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 14MHz, this function panics.
+    pub fn ck_adc(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_adc(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_ADCx clock, returns an error if the
+    /// frequency is over 14MHz.
+    pub fn try_ck_adc(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_adc = limited(freq.into().0, 0, 14_000_000, ClockDomain::Adc)?;
+        Ok(self)
+    }
+
+    /// Requires a valid 48MHz CK_USBFS clock for the USBFS peripheral.
+    ///
+    /// If the clock is not reachable, `try_freeze` returns an error.
+    pub fn require_usbfs(mut self) -> Self {
+        self.require_usbfs = true;
         self
     }
 
@@ -295,213 +680,1420 @@ impl Strict {
     /// If strictly accurate value of given `ck_sys` etc. is not reachable, this function
     /// panics.
     pub fn freeze(self, cfg: &mut CFG) -> Clocks {
-        // todo: this function is much too complex; consider split into independent parts
+        self.try_freeze(cfg).expect("invalid frequency")
+    }
+
+    /// Calculate and balance clock registers to configure into the given clock value.
+    ///
+    /// If strictly accurate value of given `ck_sys` etc. is not reachable, this function
+    /// returns an error and leaves clock registers unchanged.
+    pub fn try_freeze(self, cfg: &mut CFG) -> Result<Clocks, ClockError> {
+        let plan = self.plan()?;
+        Ok(plan.apply(cfg))
+    }
+
+    /// Calculate and balance clock registers to configure into the given clock value,
+    /// polling each oscillator or PLL stabilization flag at most `max_polls` times.
+    ///
+    /// If HXTAL or a PLL does not stabilize in time, this function turns it off and
+    /// returns `ClockError::HxtalTimeout` or `ClockError::PllTimeout`, leaving CK_SYS
+    /// unchanged. A board with a missing crystal may then boot on IRC8M with a
+    /// configurator that does not use HXTAL. If CK_SYS does not switch to the
+    /// selected source in time, it returns `ClockError::SwitchTimeout`.
+    pub fn try_freeze_timeout(self, cfg: &mut CFG, max_polls: u32) -> Result<Clocks, ClockError> {
+        let plan = self.plan()?;
+        plan.apply_timeout(cfg, max_polls)
+    }
+
+    /// Calculate and balance clock registers into the given clock value, without
+    /// writing to any register.
+    ///
+    /// If strictly accurate value of given `ck_sys` etc. is not reachable, this function
+    /// returns an error.
+    pub const fn plan(&self) -> Result<ClockPlan, ClockError> {
         const IRC8M: u32 = 8_000_000;
-        let mut usb_valid = false;
-        let target_ck_sys = self.target_ck_sys.map(|f| f.get()).unwrap_or(IRC8M);
-        let target_ck_ahb = self.target_ck_ahb.map(|f| f.get()).unwrap_or(target_ck_sys);
-        let (scs, use_pll) = match (self.hxtal, target_ck_sys) {
-            (Some(hxtal), sys) if hxtal.get() == sys => (0b01, false),
-            (None, sys) if IRC8M == sys => (0b00, false),
-            _ => (0b10, true),
+        let target_ck_sys = get_or(self.target_ck_sys, IRC8M);
+        let target_ck_ahb = get_or(self.target_ck_ahb, target_ck_sys);
+        let hxtal = match self.hxtal {
+            Some(hxtal) => Some(hxtal.get()),
+            None => None,
         };
-        let pllmf = if use_pll {
-            if let Some(hxtal) = self.hxtal {
-                let hxtal = hxtal.get();
-                let calc_pllmf = || {
-                    for div in 1..=16 {
-                        if target_ck_sys == hxtal * 13 / 2 {
-                            return 0b01101; // 6.5
-                        }
-                        let mul = target_ck_sys / (div * hxtal);
-                        if mul < 2 || mul > 32 || mul == 15 {
-                            continue;
-                        }
-                        let out_ck_sys = hxtal * mul / div;
-                        if out_ck_sys == target_ck_sys {
-                            return if mul <= 14 { mul - 2 } else { mul - 1 };
-                        }
-                    }
-                    panic!("invalid frequency")
-                };
-                calc_pllmf() as u8
+        let sys = search_ck_sys(hxtal, target_ck_sys, true);
+        if sys.ck_sys != target_ck_sys {
+            // lowest PLL output is (HXTAL / 16) * 8 / 16 * 2 or (IRC8M / 2) * 2
+            let min_ck_pll = match hxtal {
+                Some(hxtal) => hxtal / 16,
+                None => IRC8M,
+            };
+            return Err(if target_ck_sys < min_ck_pll {
+                ClockError::PllMultiplier(ClockDomain::Sys)
             } else {
-                // does not use HXTAL
-                let pllsel0_src = IRC8M / 2;
-                let mul_pllmf = target_ck_sys / pllsel0_src;
-                // pllmf: 00000 => 2, 00001 => 3, ..., 01100 => 14; 01101 => 6.5;
-                // 01111 => 16, 10000 => 17, ..., 11111 => 32;
-                // may use 6.5 here
-                let mul_pllmf = u32::max(2, u32::min(mul_pllmf, 32));
-                if target_ck_sys == mul_pllmf * pllsel0_src {
-                    // use 2..=14 | 16..=32
-                    (if mul_pllmf <= 14 {
-                        mul_pllmf - 2
-                    } else {
-                        mul_pllmf - 1
-                    }) as u8
-                } else if target_ck_sys == pllsel0_src * 13 / 2 {
-                    0b01101 as u8 // use special 6.5 multiplier
-                } else {
-                    panic!("invalid frequency")
-                }
-            }
-        } else {
-            0 // placeholder, not use_pll
+                ClockError::NoDivider(ClockDomain::Sys)
+            });
+        }
+        let target_ck_i2s = get_or(self.target_ck_i2s, target_ck_sys);
+        let i2s = search_ck_i2s(hxtal, sys, target_ck_i2s, true);
+        if i2s.ck_i2s != target_ck_i2s {
+            return Err(ClockError::NoDivider(ClockDomain::I2s));
+        }
+        let (ahbpsc, ahb_shr) = match strict_ahbpsc(target_ck_sys, target_ck_ahb) {
+            Ok(ans) => ans,
+            Err(e) => return Err(e),
         };
-        let (ahbpsc, ahb_shr) = {
-            // 0xxx: /1; 1000: /2; 1001: /4; ... 1111: /512. (skip /32)
-            let mut ahb_shr = 0; // log2(1)
-            let mut ans = 0b0111u8;
-            let mut target_freq = target_ck_ahb;
-            while ahb_shr <= 9 {
-                // log2(512)
-                if ahb_shr != 5 && target_freq == target_ck_sys {
-                    break;
-                }
-                target_freq *= 2;
-                ahb_shr += 1;
-                if ahb_shr != 5 {
-                    // log2(32)
-                    ans += 1;
-                }
-            }
-            if ans > 0b1111 {
-                panic!("invalid frequency")
-            }
-            (ans, ahb_shr)
+        let target_ck_apb1 = get_or(self.target_ck_apb1, target_ck_ahb / 2);
+        let apb1psc = match strict_apbpsc(target_ck_ahb, target_ck_apb1, ClockDomain::Apb1) {
+            Ok(ans) => ans,
+            Err(e) => return Err(e),
         };
-        let calc_psc_apbx = |target_ck_apbx: u32| {
-            let mut ans = 0b011u8;
-            let mut target_freq = target_ck_apbx;
-            while ans <= 0b111 {
-                if target_freq == target_ck_ahb {
-                    break;
-                }
-                target_freq *= 2;
-                ans += 1;
-            }
-            if ans > 0b111 {
-                panic!("invalid frequency")
-            };
-            ans
+        let target_ck_apb2 = get_or(self.target_ck_apb2, target_ck_ahb);
+        let apb2psc = match strict_apbpsc(target_ck_ahb, target_ck_apb2, ClockDomain::Apb2) {
+            Ok(ans) => ans,
+            Err(e) => return Err(e),
         };
-        let target_ck_apb1 = self
-            .target_ck_apb1
-            .map(|f| f.get())
-            .unwrap_or(target_ck_ahb / 2);
-        let apb1psc = calc_psc_apbx(target_ck_apb1);
-        let target_ck_apb2 = self
-            .target_ck_apb2
-            .map(|f| f.get())
-            .unwrap_or(target_ck_ahb);
-        let apb2psc = calc_psc_apbx(target_ck_apb2);
-        let target_ck_adc = self
-            .target_ck_adc
-            .map(|f| f.get())
-            .unwrap_or(target_ck_apb2 / 8);
-        let adcpsc = if target_ck_adc * 2 == target_ck_apb2 {
-            0b000 /* alias: 0b100 */
-        } else if target_ck_adc * 4 == target_ck_apb2 {
-            0b001
-        } else if target_ck_adc * 6 == target_ck_apb2 {
-            0b010
-        } else if target_ck_adc * 8 == target_ck_apb2 {
-            0b011 /* alias: 0b110 */
-        } else if target_ck_adc * 12 == target_ck_apb2 {
-            0b101
-        } else if target_ck_adc * 16 == target_ck_apb2 {
-            0b111
-        } else {
-            panic!("invalid freqency")
+        let target_ck_adc = get_or(self.target_ck_adc, target_ck_apb2 / 8);
+        let (adcpsc, adc_div) = match strict_adcpsc(target_ck_apb2, target_ck_adc) {
+            Ok(ans) => ans,
+            Err(e) => return Err(e),
         };
-        // 1. enable IRC8M
-        if self.hxtal.is_none() {
-            // enable IRC8M
-            cfg.ctl().modify(|_, w| w.irc8men().set_bit());
-            // Wait for oscillator to stabilize
-            while cfg.ctl().read().irc8mstb().bit_is_clear() {}
-        }
-        // 2. enable hxtal
-        if self.hxtal.is_some() {
-            // enable hxtal
-            cfg.ctl().modify(|_, w| w.hxtalen().set_bit());
-            // wait before stable
-            while cfg.ctl().read().hxtalstb().bit_is_clear() {}
+        let usbfspsc = match sys.pll {
+            Some(PllConfig { pllsel: true, .. }) => calc_usbfspsc(target_ck_sys),
+            _ => None,
+        };
+        if self.require_usbfs && usbfspsc.is_none() {
+            return Err(ClockError::NoDivider(ClockDomain::Usb));
         }
-        // 3. enable pll
-        if use_pll {
-            cfg.cfg0().modify(|_, w| unsafe {
-                // Configure PLL input selector
-                w.pllsel().bit(use_pll);
-                // Configure PLL multiplier
-                w.pllmf_4().bit(pllmf & 0x10 != 0);
-                w.pllmf_3_0().bits(pllmf & 0xf)
-            });
-            // Enable PLL
-            cfg.ctl().modify(|_, w| w.pllen().set_bit());
-            // Wait for PLL to stabilize
-            while cfg.ctl().read().pllstb().bit_is_clear() {}
-        } else {
-            // or we disable PLL
-            cfg.ctl().modify(|_, w| w.pllen().clear_bit());
+        Ok(ClockPlan {
+            hxtal: hxtal.is_some(),
+            scs: sys.scs,
+            pll: sys.pll,
+            predv1: i2s.predv1,
+            pll1mf: sys.pll1mf,
+            pll2mf: i2s.pll2mf,
+            i2ssel: i2s.pll2mf.is_some(),
+            ahbpsc,
+            apb1psc,
+            apb2psc,
+            adcpsc,
+            usbfspsc,
+            clocks: Clocks {
+                ck_sys: Hertz(target_ck_sys),
+                ahb_shr,
+                apb1_shr: apb1psc - 0b011,
+                apb2_shr: apb2psc - 0b011,
+                adc_div,
+                usb_valid: usbfspsc.is_some(),
+                ck_i2s1: Hertz(i2s.ck_i2s),
+                ck_i2s2: Hertz(i2s.ck_i2s),
+            },
+        })
+    }
+}
+
+// Returns (AHBPSC, right shift) that strictly divides CK_SYS into CK_AHB
+const fn strict_ahbpsc(ck_sys: u32, target_ck_ahb: u32) -> Result<(u8, u8), ClockError> {
+    let mut i = 0;
+    while i < AHB_SHR.len() {
+        let shr = AHB_SHR[i];
+        if (target_ck_ahb as u64) << shr == ck_sys as u64 {
+            return Ok((ahbpsc_bits(shr), shr));
         }
-        // 4. check SCS selector
-        cfg.cfg0().modify(|_, w| unsafe { w.scs().bits(scs) });
-        // 5. check and enable usb
-        if self.hxtal.is_some() {
-            let ck_pll = target_ck_sys;
-            let (usb_freq_okay, usbfspsc) = match ck_pll {
-                48_000_000 => (true, 0b01), // ck_pll / 1
-                72_000_000 => (true, 0b00), // ck_pll / 1.5
-                96_000_000 => (true, 0b11), // ck_pll / 2
-                // 0b10 (ck_pll / 2.5) is impossible in this algorithm
-                _ => (false, 0),
-            };
-            usb_valid = usb_freq_okay;
-            // adjust USB prescaler
-            cfg.cfg0()
-                .modify(|_, w| unsafe { w.usbfspsc().bits(usbfspsc) });
+        i += 1;
+    }
+    Err(ClockError::NoDivider(ClockDomain::Ahb))
+}
+
+// Returns APBxPSC that strictly divides CK_AHB into CK_APBx
+const fn strict_apbpsc(
+    ck_ahb: u32,
+    target_ck_apbx: u32,
+    domain: ClockDomain,
+) -> Result<u8, ClockError> {
+    let mut i = 0;
+    while i < APB_SHR.len() {
+        let shr = APB_SHR[i];
+        if (target_ck_apbx as u64) << shr == ck_ahb as u64 {
+            return Ok(0b011 + shr);
         }
-        // todo: verify if three switches in one modify is okay
-        cfg.cfg0().modify(|_, w| unsafe {
-            // 6. adjust AHB and APB clocks
-            w.ahbpsc().bits(ahbpsc);
-            w.apb1psc().bits(apb1psc);
-            w.apb2psc().bits(apb2psc);
-            // 7. adjust ADC clocks
-            w.adcpsc_2().bit(adcpsc & 0b100 != 0);
-            w.adcpsc_1_0().bits(adcpsc & 0b11)
-        });
-        Clocks {
-            ck_sys: Hertz(target_ck_sys),
-            ahb_shr,
-            apb1_shr: apb1psc - 0b011,
-            apb2_shr: apb2psc - 0b011,
-            adc_div: (target_ck_apb2 / target_ck_adc) as u8,
-            usb_valid,
+        i += 1;
+    }
+    Err(ClockError::NoDivider(domain))
+}
+
+// Returns (ADCPSC, divider) that strictly divides CK_APB2 into CK_ADCx
+const fn strict_adcpsc(ck_apb2: u32, target_ck_adc: u32) -> Result<(u8, u8), ClockError> {
+    let mut i = 0;
+    while i < ADC_DIV.len() {
+        let (bits, div) = ADC_DIV[i];
+        if target_ck_adc * div == ck_apb2 {
+            return Ok((bits, div as u8));
         }
+        i += 1;
     }
+    Err(ClockError::NoDivider(ClockDomain::Adc))
 }
 
-/// (TODO) Precise clock configurator
+/// Precise clock configurator
+///
+/// This configurator offers config to get the most precise output value possible
+/// using input values. Errors between desired and actual output are acceptible;
+/// they are minimized by the algorithm, thus the output would be as precise as
+/// possible. The actual errors are returned by `freeze` as a `Deviation`.
 ///
-/// This configurator would offer config to get the most precise output value possible
-/// using input values. Errors between desired and actual output would be acceptible;
-/// it would be minimized by the algorithm, thus the output would be as precise as
-/// possible.
+/// Unset AHB, APB1, APB2 and ADC targets default to the same ratios as `Strict`,
+/// but are derived from the actually reachable frequencies.
+#[derive(Default)]
 pub struct Precise {
-    _todo: (),
+    hxtal: Option<NonZeroU32>,
+    target_ck_sys: Option<NonZeroU32>,
+    target_ck_i2s: Option<NonZeroU32>,
+    target_ck_ahb: Option<NonZeroU32>,
+    target_ck_apb1: Option<NonZeroU32>,
+    target_ck_apb2: Option<NonZeroU32>,
+    target_ck_adc: Option<NonZeroU32>,
 }
 
-/// Opaque `BDCTL` register
-pub struct BDCTL {
-    _ownership: (),
+/// Frequency errors of a `Precise` configuration
+///
+/// Each field is the actual frequency minus the desired frequency, in hertz.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Deviation {
+    /// Error of the CK_SYS clock
+    pub ck_sys: i32,
+    /// Error of the CK_AHB clock
+    pub ck_ahb: i32,
+    /// Error of the CK_APB1 clock
+    pub ck_apb1: i32,
+    /// Error of the CK_APB2 clock
+    pub ck_apb2: i32,
+    /// Error of the CK_ADCx clock
+    pub ck_adc: i32,
+    /// Error of the CK_I2S clock
+    pub ck_i2s: i32,
 }
 
-impl BDCTL {
-    #[inline]
-    pub(crate) fn bdctl(&mut self) -> &rcu::BDCTL {
-        unsafe { &(*RCU::ptr()).bdctl }
+impl Precise {
+    /// Create a configurator
+    pub const fn new() -> Self {
+        Precise {
+            hxtal: None,
+            target_ck_sys: None,
+            target_ck_i2s: None,
+            target_ck_ahb: None,
+            target_ck_apb1: None,
+            target_ck_apb2: None,
+            target_ck_adc: None,
+        }
+    }
+
+    /// Prefer use HXTAL (external oscillator) as the clock source.
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is out of 4MHz to 32MHz, this function panics.
+    pub fn use_hxtal(self, freq: impl Into<Hertz>) -> Self {
+        self.try_use_hxtal(freq).expect("invalid frequency")
+    }
+
+    /// Prefer use HXTAL (external oscillator) as the clock source, returns an
+    /// error if the frequency is out of 4MHz to 32MHz.
+    pub fn try_use_hxtal(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.hxtal = limited(freq.into().0, 4_000_000, 32_000_000, ClockDomain::Hxtal)?;
+        Ok(self)
+    }
+
+    /// Sets the desired frequency for the CK_SYS clock
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_sys(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_sys(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_SYS clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_sys(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_sys = limited(freq.into().0, 0, 108_000_000, ClockDomain::Sys)?;
+        Ok(self)
+    }
+
+    /// Sets the desired frequency for the CK_I2S clock of both I2S1 and I2S2
+    ///
+    /// CK_I2S is clocked by CK_SYS, or by CK_PLL2 * 2 which needs HXTAL.
+    pub fn ck_i2s(mut self, freq: impl Into<Hertz>) -> Self {
+        let freq_hz = freq.into().0;
+        self.target_ck_i2s = NonZeroU32::new(freq_hz);
+        self
+    }
+
+    /// Sets the desired frequency for the CK_AHB clock
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_ahb(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_ahb(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_AHB clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_ahb(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_ahb = limited(freq.into().0, 0, 108_000_000, ClockDomain::Ahb)?;
+        Ok(self)
+    }
+
+    /// Sets the desired frequency for the CK_APB1 clock
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 54MHz, this function panics.
+    pub fn ck_apb1(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_apb1(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_APB1 clock, returns an error if the
+    /// frequency is over 54MHz.
+    pub fn try_ck_apb1(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_apb1 = limited(freq.into().0, 0, 54_000_000, ClockDomain::Apb1)?;
+        Ok(self)
+    }
+
+    /// Sets the desired frequency for the CK_APB2 clock
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 108MHz, this function panics.
+    pub fn ck_apb2(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_apb2(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_APB2 clock, returns an error if the
+    /// frequency is over 108MHz.
+    pub fn try_ck_apb2(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_apb2 = limited(freq.into().0, 0, 108_000_000, ClockDomain::Apb2)?;
+        Ok(self)
+    }
+
+    /// Sets the desired frequency for the CK_ADCx clock
+    ///
+    /// # Panics
+    ///
+    /// If the frequency is over 14MHz, this function panics.
+    pub fn ck_adc(self, freq: impl Into<Hertz>) -> Self {
+        self.try_ck_adc(freq).expect("invalid frequency")
+    }
+
+    /// Sets the desired frequency for the CK_ADCx clock, returns an error if the
+    /// frequency is over 14MHz.
+    pub fn try_ck_adc(mut self, freq: impl Into<Hertz>) -> Result<Self, ClockError> {
+        self.target_ck_adc = limited(freq.into().0, 0, 14_000_000, ClockDomain::Adc)?;
+        Ok(self)
+    }
+
+    /// Search for the closest reachable frequencies and configure clock registers
+    /// into them.
+    ///
+    /// Returns the frozen clocks, and the error between each actual and desired
+    /// frequency.
+    pub fn freeze(self, cfg: &mut CFG) -> (Clocks, Deviation) {
+        let (plan, deviation) = self.plan();
+        (plan.apply(cfg), deviation)
+    }
+
+    /// Search for the closest reachable frequencies without writing to any register.
+    ///
+    /// Returns the plan to configure clock registers into, and the error between
+    /// each actual and desired frequency.
+    pub const fn plan(&self) -> (ClockPlan, Deviation) {
+        const IRC8M: u32 = 8_000_000;
+        let hxtal = match self.hxtal {
+            Some(hxtal) => Some(hxtal.get()),
+            None => None,
+        };
+        let target_ck_sys = get_or(self.target_ck_sys, IRC8M);
+        let sys = search_ck_sys(hxtal, target_ck_sys, false);
+        let ck_sys = sys.ck_sys;
+        let target_ck_i2s = get_or(self.target_ck_i2s, ck_sys);
+        let i2s = search_ck_i2s(hxtal, sys, target_ck_i2s, false);
+        let target_ck_ahb = get_or(self.target_ck_ahb, ck_sys);
+        let ahb_shr = search_shr(ck_sys, target_ck_ahb, 108_000_000, &AHB_SHR);
+        let ck_ahb = ck_sys >> ahb_shr;
+        let target_ck_apb1 = get_or(self.target_ck_apb1, ck_ahb / 2);
+        let apb1_shr = search_shr(ck_ahb, target_ck_apb1, 54_000_000, &APB_SHR);
+        let ck_apb1 = ck_ahb >> apb1_shr;
+        let target_ck_apb2 = get_or(self.target_ck_apb2, ck_ahb);
+        let apb2_shr = search_shr(ck_ahb, target_ck_apb2, 108_000_000, &APB_SHR);
+        let ck_apb2 = ck_ahb >> apb2_shr;
+        let target_ck_adc = get_or(self.target_ck_adc, ck_apb2 / 8);
+        let (adcpsc, adc_div) = search_adcpsc(ck_apb2, target_ck_adc);
+        let ck_adc = ck_apb2 / adc_div as u32;
+        // USBFS clock is only valid when PLL is clocked by HXTAL
+        let usbfspsc = match sys.pll {
+            Some(PllConfig { pllsel: true, .. }) => calc_usbfspsc(ck_sys),
+            _ => None,
+        };
+        let plan = ClockPlan {
+            hxtal: hxtal.is_some(),
+            scs: sys.scs,
+            pll: sys.pll,
+            predv1: i2s.predv1,
+            pll1mf: sys.pll1mf,
+            pll2mf: i2s.pll2mf,
+            i2ssel: i2s.pll2mf.is_some(),
+            ahbpsc: ahbpsc_bits(ahb_shr),
+            apb1psc: 0b011 + apb1_shr,
+            apb2psc: 0b011 + apb2_shr,
+            adcpsc,
+            usbfspsc,
+            clocks: Clocks {
+                ck_sys: Hertz(ck_sys),
+                ahb_shr,
+                apb1_shr,
+                apb2_shr,
+                adc_div,
+                usb_valid: usbfspsc.is_some(),
+                ck_i2s1: Hertz(i2s.ck_i2s),
+                ck_i2s2: Hertz(i2s.ck_i2s),
+            },
+        };
+        let deviation = Deviation {
+            ck_sys: ck_sys as i32 - target_ck_sys as i32,
+            ck_ahb: ck_ahb as i32 - target_ck_ahb as i32,
+            ck_apb1: ck_apb1 as i32 - target_ck_apb1 as i32,
+            ck_apb2: ck_apb2 as i32 - target_ck_apb2 as i32,
+            ck_adc: ck_adc as i32 - target_ck_adc as i32,
+            ck_i2s: i2s.ck_i2s as i32 - target_ck_i2s as i32,
+        };
+        (plan, deviation)
+    }
+}
+
+/// Resolved clock tree configuration
+///
+/// A plan holds the register values computed by a clock configurator, together with
+/// the resulting `Clocks`. Computing a plan does not touch the hardware; it's done
+/// by `const fn`s, so a plan can be checked on the host or in a `const` context.
+/// Use `apply` to configure clock registers into the plan.
+#[derive(Clone, Copy)]
+pub struct ClockPlan {
+    hxtal: bool,
+    scs: u8,
+    pll: Option<PllConfig>,
+    predv1: u8,
+    pll1mf: Option<u8>,
+    pll2mf: Option<u8>,
+    i2ssel: bool,
+    ahbpsc: u8,
+    apb1psc: u8,
+    apb2psc: u8,
+    adcpsc: u8,
+    usbfspsc: Option<u8>,
+    clocks: Clocks,
+}
+
+impl ClockPlan {
+    /// Returns whether HXTAL is enabled by this plan
+    pub const fn hxtal(&self) -> bool {
+        self.hxtal
+    }
+
+    /// Returns the SCS\[1:0\] bits; 0b00 for IRC8M, 0b01 for HXTAL, 0b10 for CK_PLL
+    pub const fn scs(&self) -> u8 {
+        self.scs
+    }
+
+    /// Returns the PLL configuration, or `None` if PLL is disabled
+    pub const fn pll(&self) -> Option<PllConfig> {
+        self.pll
+    }
+
+    /// Returns the PREDV1\[3:0\] bits, which is the divider minus one
+    pub const fn predv1(&self) -> u8 {
+        self.predv1
+    }
+
+    /// Returns the PLL1MF\[3:0\] bits, or `None` if PLL1 is disabled
+    pub const fn pll1mf(&self) -> Option<u8> {
+        self.pll1mf
+    }
+
+    /// Returns the PLL2MF\[3:0\] bits, or `None` if PLL2 is disabled
+    pub const fn pll2mf(&self) -> Option<u8> {
+        self.pll2mf
+    }
+
+    /// Returns the I2S1SEL and I2S2SEL bits; `false` for CK_SYS, `true` for
+    /// CK_PLL2 * 2
+    pub const fn i2ssel(&self) -> bool {
+        self.i2ssel
+    }
+
+    /// Returns the AHBPSC\[3:0\] bits
+    pub const fn ahbpsc(&self) -> u8 {
+        self.ahbpsc
+    }
+
+    /// Returns the APB1PSC\[2:0\] bits
+    pub const fn apb1psc(&self) -> u8 {
+        self.apb1psc
+    }
+
+    /// Returns the APB2PSC\[2:0\] bits
+    pub const fn apb2psc(&self) -> u8 {
+        self.apb2psc
+    }
+
+    /// Returns the ADCPSC\[2:0\] bits
+    pub const fn adcpsc(&self) -> u8 {
+        self.adcpsc
+    }
+
+    /// Returns the USBFSPSC\[1:0\] bits, or `None` if CK_USBFS is not valid
+    pub const fn usbfspsc(&self) -> Option<u8> {
+        self.usbfspsc
+    }
+
+    /// Returns the clock frequencies after this plan is applied
+    pub const fn clocks(&self) -> Clocks {
+        self.clocks
+    }
+
+    /// Configure clock registers into this plan, returns the frozen clocks.
+    ///
+    /// This function waits for oscillators and PLLs to stabilize without a limit;
+    /// use `apply_timeout` if they may never be stable, e.g. the crystal is missing.
+    pub fn apply(self, cfg: &mut CFG) -> Clocks {
+        match self.apply_with(cfg, None) {
+            Ok(clocks) => clocks,
+            Err(_) => unreachable!("no timeout is configured"),
+        }
+    }
+
+    /// Configure clock registers into this plan, returns the frozen clocks.
+    ///
+    /// Each oscillator or PLL stabilization flag, and the CK_SYS switch status, is
+    /// polled at most `max_polls` times. On timeout, the failed oscillator or PLL is
+    /// turned off, CK_SYS is kept unchanged (IRC8M after reset, or IRC8M if it was
+    /// clocked by the PLL), and this function returns `HxtalTimeout`, `PllTimeout`
+    /// or `Irc8mTimeout`. If CK_SYS does not switch in time, it returns
+    /// `SwitchTimeout`.
+    pub fn apply_timeout(self, cfg: &mut CFG, max_polls: u32) -> Result<Clocks, ClockError> {
+        self.apply_with(cfg, Some(max_polls))
+    }
+
+    /// Switch the running clocks into this plan, returns the new clocks.
+    ///
+    /// This function could be called at any time after the clocks are frozen, e.g.
+    /// to drop to IRC8M while idle and go back to the PLL under load. CK_SYS runs
+    /// on IRC8M while the PLLs are reconfigured, and HXTAL, PLL1 and PLL2 are turned
+    /// off afterwards if this plan does not use them.
+    ///
+    /// Peripherals configured with the previous clocks keep their old dividers; update
+    /// them with the returned clocks, e.g. by `Serial::reconfigure` or `Spi::reconfigure`.
+    pub fn switch(self, cfg: &mut CFG) -> Clocks {
+        let (hxtal, pll1, pll2) = (self.hxtal, self.pll1mf.is_some(), self.pll2mf.is_some());
+        let clocks = self.apply(cfg);
+        cfg.ctl().modify(|_, w| {
+            w.pll1en().bit(pll1);
+            w.pll2en().bit(pll2);
+            w.hxtalen().bit(hxtal)
+        });
+        clocks
+    }
+
+    fn apply_with(self, cfg: &mut CFG, max_polls: Option<u32>) -> Result<Clocks, ClockError> {
+        // 1. enable IRC8M or HXTAL
+        if self.hxtal {
+            cfg.ctl().modify(|_, w| w.hxtalen().set_bit());
+            // wait before stable
+            if !poll(max_polls, || cfg.ctl().read().hxtalstb().bit_is_set()) {
+                cfg.ctl().modify(|_, w| w.hxtalen().clear_bit());
+                return Err(ClockError::HxtalTimeout);
+            }
+        } else {
+            cfg.ctl().modify(|_, w| w.irc8men().set_bit());
+            // Wait for oscillator to stabilize
+            if !poll(max_polls, || cfg.ctl().read().irc8mstb().bit_is_set()) {
+                return Err(ClockError::Irc8mTimeout);
+            }
+        }
+        // 2. CK_SYS should not be clocked by the PLL while it's reconfigured or
+        // prescalers are changed, run on IRC8M meanwhile
+        if cfg.cfg0().read().scss().bits() == 0b10 {
+            cfg.ctl().modify(|_, w| w.irc8men().set_bit());
+            if !poll(max_polls, || cfg.ctl().read().irc8mstb().bit_is_set()) {
+                return Err(ClockError::Irc8mTimeout);
+            }
+            cfg.cfg0().modify(|_, w| unsafe { w.scs().bits(0b00) });
+            if !poll(max_polls, || cfg.cfg0().read().scss().bits() == 0b00) {
+                return Err(ClockError::SwitchTimeout);
+            }
+        }
+        // PLL should be disabled before its configuration or source is changed
+        if self.pll.is_some() {
+            cfg.ctl().modify(|_, w| w.pllen().clear_bit());
+        }
+        // 3. enable pll1 and pll2, both are clocked by HXTAL / PREDV1
+        if self.pll1mf.is_some() || self.pll2mf.is_some() {
+            cfg.ctl()
+                .modify(|_, w| w.pll1en().clear_bit().pll2en().clear_bit());
+            cfg.cfg1().modify(|_, w| unsafe {
+                w.predv1().bits(self.predv1);
+                if let Some(pll1mf) = self.pll1mf {
+                    w.pll1mf().bits(pll1mf);
+                }
+                if let Some(pll2mf) = self.pll2mf {
+                    w.pll2mf().bits(pll2mf);
+                }
+                w
+            });
+            cfg.ctl().modify(|_, w| {
+                w.pll1en().bit(self.pll1mf.is_some());
+                w.pll2en().bit(self.pll2mf.is_some())
+            });
+            let pll1_ready = self.pll1mf.is_none()
+                || poll(max_polls, || cfg.ctl().read().pll1stb().bit_is_set());
+            let pll2_ready = self.pll2mf.is_none()
+                || poll(max_polls, || cfg.ctl().read().pll2stb().bit_is_set());
+            if !pll1_ready || !pll2_ready {
+                cfg.ctl()
+                    .modify(|_, w| w.pll1en().clear_bit().pll2en().clear_bit());
+                return Err(ClockError::PllTimeout);
+            }
+        }
+        // 4. enable pll
+        if let Some(pll) = self.pll {
+            // Configure PREDV0 input selector and divider
+            cfg.cfg1().modify(|_, w| unsafe {
+                w.predv0sel().bit(pll.predv0sel);
+                w.predv0().bits(pll.predv0)
+            });
+            cfg.cfg0().modify(|_, w| unsafe {
+                // Configure PLL input selector
+                w.pllsel().bit(pll.pllsel);
+                // Configure PLL multiplier
+                w.pllmf_4().bit(pll.pllmf & 0x10 != 0);
+                w.pllmf_3_0().bits(pll.pllmf & 0xf)
+            });
+            // Enable PLL
+            cfg.ctl().modify(|_, w| w.pllen().set_bit());
+            // Wait for PLL to stabilize
+            if !poll(max_polls, || cfg.ctl().read().pllstb().bit_is_set()) {
+                cfg.ctl().modify(|_, w| w.pllen().clear_bit());
+                return Err(ClockError::PllTimeout);
+            }
+        }
+        // 5. select I2S clock source
+        cfg.cfg1()
+            .modify(|_, w| w.i2s1sel().bit(self.i2ssel).i2s2sel().bit(self.i2ssel));
+        // 6. adjust USB prescaler before the clock is selected
+        if let Some(usbfspsc) = self.usbfspsc {
+            cfg.cfg0()
+                .modify(|_, w| unsafe { w.usbfspsc().bits(usbfspsc) });
+        }
+        cfg.cfg0().modify(|_, w| unsafe {
+            // 7. adjust AHB and APB clocks
+            w.ahbpsc().bits(self.ahbpsc);
+            w.apb1psc().bits(self.apb1psc);
+            w.apb2psc().bits(self.apb2psc);
+            // 8. adjust ADC clocks
+            w.adcpsc_2().bit(self.adcpsc & 0b100 != 0);
+            w.adcpsc_1_0().bits(self.adcpsc & 0b11)
+        });
+        // 9. check SCS selector
+        cfg.cfg0().modify(|_, w| unsafe { w.scs().bits(self.scs) });
+        if !poll(max_polls, || cfg.cfg0().read().scss().bits() == self.scs) {
+            return Err(ClockError::SwitchTimeout);
+        }
+        if self.pll.is_none() {
+            // or we disable PLL
+            cfg.ctl().modify(|_, w| w.pllen().clear_bit());
+        }
+        Ok(self.clocks)
+    }
+}
+
+// Polls `ready` until it returns true, at most `max_polls` times if it's not `None`.
+// Returns whether `ready` has returned true.
+#[inline]
+fn poll(max_polls: Option<u32>, mut ready: impl FnMut() -> bool) -> bool {
+    match max_polls {
+        Some(max_polls) => (0..max_polls).any(|_| ready()),
+        None => {
+            while !ready() {}
+            true
+        }
+    }
+}
+
+/// PLL configuration in a `ClockPlan`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PllConfig {
+    pllsel: bool,
+    predv0sel: bool,
+    predv0: u8,
+    pllmf: u8,
+}
+
+impl PllConfig {
+    /// Returns the PLLSEL bit; `false` for IRC8M / 2, `true` for PREDV0
+    pub const fn pllsel(&self) -> bool {
+        self.pllsel
+    }
+
+    /// Returns the PREDV0SEL bit; `false` for HXTAL, `true` for CK_PLL1
+    pub const fn predv0sel(&self) -> bool {
+        self.predv0sel
+    }
+
+    /// Returns the PREDV0\[3:0\] bits, which is the divider minus one
+    pub const fn predv0(&self) -> u8 {
+        self.predv0
+    }
+
+    /// Returns the PLLMF\[4:0\] bits
+    pub const fn pllmf(&self) -> u8 {
+        self.pllmf
+    }
+}
+
+#[inline]
+const fn get_or(freq: Option<NonZeroU32>, default: u32) -> u32 {
+    match freq {
+        Some(freq) => freq.get(),
+        None => default,
+    }
+}
+
+// Right shifts of CK_SYS allowed by AHB prescaler (/32 is not available)
+const AHB_SHR: [u8; 9] = [0, 1, 2, 3, 4, 6, 7, 8, 9];
+
+// Right shifts of CK_AHB allowed by APB1 and APB2 prescalers
+const APB_SHR: [u8; 5] = [0, 1, 2, 3, 4];
+
+// ADC prescalers: (ADCPSC[2:0], divider)
+const ADC_DIV: [(u8, u32); 6] = [
+    (0b000, 2), // alias: 0b100
+    (0b001, 4),
+    (0b010, 6),
+    (0b011, 8), // alias: 0b110
+    (0b101, 12),
+    (0b111, 16),
+];
+
+// AHB prescaler: 0xxx: /1; 1000: /2; 1001: /4; ... 1111: /512. (skip /32)
+#[inline]
+const fn ahbpsc_bits(ahb_shr: u8) -> u8 {
+    if ahb_shr == 0 {
+        0b0111
+    } else if ahb_shr < 5 {
+        0b0111 + ahb_shr
+    } else {
+        0b0110 + ahb_shr
+    }
+}
+
+// USBFS prescaler to get 48MHz CK_USBFS from CK_PLL
+#[inline]
+const fn calc_usbfspsc(ck_pll: u32) -> Option<u8> {
+    match ck_pll {
+        48_000_000 => Some(0b01), // ck_pll / 1
+        72_000_000 => Some(0b00), // ck_pll / 1.5
+        96_000_000 => Some(0b11), // ck_pll / 2
+        // 0b10 (ck_pll / 2.5) would need 120MHz which is over the limit
+        _ => None,
+    }
+}
+
+// PLL1 and PLL2 multipliers: (PLL1MF[3:0] or PLL2MF[3:0], multiplier)
+const PLL12MF: [(u8, u32); 9] = [
+    (0b0110, 8),
+    (0b0111, 9),
+    (0b1000, 10),
+    (0b1001, 11),
+    (0b1010, 12),
+    (0b1011, 13),
+    (0b1100, 14),
+    (0b1110, 16),
+    (0b1111, 20),
+];
+
+// Multiplier of PLL1MF[3:0] or PLL2MF[3:0]; reserved values read as zero
+const fn pll12mf_mul(bits: u8) -> u32 {
+    let mut i = 0;
+    while i < PLL12MF.len() {
+        if PLL12MF[i].0 == bits {
+            return PLL12MF[i].1;
+        }
+        i += 1;
+    }
+    0
+}
+
+// Resolved source of the CK_SYS clock
+#[derive(Clone, Copy)]
+struct SysSource {
+    ck_sys: u32,
+    // 0b00: IRC8M; 0b01: HXTAL; 0b10: CK_PLL
+    scs: u8,
+    pll: Option<PllConfig>,
+    // PREDV1[3:0], only valid if PLL1 is used
+    predv1: u8,
+    // PLL1MF[3:0], or `None` if PLL1 is not used
+    pll1mf: Option<u8>,
+}
+
+// Clock into the PREDV0 divider (or IRC8M / 2), in `num / den` Hz
+#[derive(Clone, Copy)]
+struct PllInput {
+    num: u64,
+    den: u64,
+    pllsel: bool,
+    predv0sel: bool,
+    max_predv0: u64,
+    predv1: u8,
+    pll1mf: Option<u8>,
+}
+
+// PLL multiplier for PLLMF[4:0], doubled to represent the 6.5 multiplier.
+//
+// 00000 => 2, 00001 => 3, ..., 01100 => 14; 01101 => 6.5;
+// 01110 and 01111 => 16, 10000 => 17, ..., 11111 => 32.
+const fn pllmf_mul2(pllmf: u8) -> u32 {
+    match pllmf {
+        0b00000..=0b01100 => (pllmf as u32 + 2) * 2,
+        0b01101 => 13,
+        0b01110 => 32,
+        _ => (pllmf as u32 + 1) * 2,
+    }
+}
+
+// Search all CK_SYS sources for the frequency closest to `target`. If `exact` is
+// set, frequencies that are not whole hertz are skipped.
+//
+// If HXTAL is not present, the PLL is clocked by IRC8M / 2; otherwise it's clocked
+// by HXTAL / PREDV0, or by CK_PLL1 / PREDV0 where CK_PLL1 = HXTAL / PREDV1 * PLL1MF.
+// Simpler sources are preferred when errors are equal.
+const fn search_ck_sys(hxtal: Option<u32>, target: u32, exact: bool) -> SysSource {
+    const IRC8M: u32 = 8_000_000;
+    let hxtal = match hxtal {
+        Some(hxtal) => hxtal,
+        None => {
+            let direct = SysSource {
+                ck_sys: IRC8M,
+                scs: 0b00,
+                pll: None,
+                predv1: 0,
+                pll1mf: None,
+            };
+            let input = PllInput {
+                num: IRC8M as u64 / 2,
+                den: 1,
+                pllsel: false,
+                predv0sel: false,
+                max_predv0: 1,
+                predv1: 0,
+                pll1mf: None,
+            };
+            return search_pll(direct, input, target, exact);
+        }
+    };
+    let direct = SysSource {
+        ck_sys: hxtal,
+        scs: 0b01,
+        pll: None,
+        predv1: 0,
+        pll1mf: None,
+    };
+    let input = PllInput {
+        num: hxtal as u64,
+        den: 1,
+        pllsel: true,
+        predv0sel: false,
+        max_predv0: 16,
+        predv1: 0,
+        pll1mf: None,
+    };
+    let mut best = search_pll(direct, input, target, exact);
+    // only turn to PLL1 if PREDV0 alone does not fit
+    let mut predv1 = 1;
+    while predv1 <= 16 && best.ck_sys != target {
+        let mut i = 0;
+        while i < PLL12MF.len() {
+            let (pll1mf, mul) = PLL12MF[i];
+            let input = PllInput {
+                num: hxtal as u64 * mul as u64,
+                den: predv1,
+                pllsel: true,
+                predv0sel: true,
+                max_predv0: 16,
+                predv1: (predv1 - 1) as u8,
+                pll1mf: Some(pll1mf),
+            };
+            best = search_pll(best, input, target, exact);
+            i += 1;
+        }
+        predv1 += 1;
+    }
+    best
+}
+
+// Search PREDV0 and PLLMF for the CK_PLL frequency closest to `target` from `input`,
+// returns `best` if no better frequency is found.
+const fn search_pll(mut best: SysSource, input: PllInput, target: u32, exact: bool) -> SysSource {
+    let mut predv0 = 1;
+    while predv0 <= input.max_predv0 {
+        let mut pllmf = 0;
+        while pllmf <= 0b11111 {
+            let num = input.num * pllmf_mul2(pllmf) as u64;
+            let den = input.den * predv0 * 2;
+            let ck_pll = num / den;
+            // 0b01110 is an alias of 0b01111
+            if pllmf != 0b01110
+                && (!exact || num % den == 0)
+                && ck_pll <= 108_000_000
+                && abs_diff(ck_pll as u32, target) < abs_diff(best.ck_sys, target)
+            {
+                best = SysSource {
+                    ck_sys: ck_pll as u32,
+                    scs: 0b10,
+                    pll: Some(PllConfig {
+                        pllsel: input.pllsel,
+                        predv0sel: input.predv0sel,
+                        predv0: (predv0 - 1) as u8,
+                        pllmf,
+                    }),
+                    predv1: input.predv1,
+                    pll1mf: input.pll1mf,
+                };
+            }
+            pllmf += 1;
+        }
+        predv0 += 1;
+    }
+    best
+}
+
+// Resolved source of the CK_I2S clock
+#[derive(Clone, Copy)]
+struct I2sSource {
+    ck_i2s: u32,
+    // PREDV1[3:0], shared with PLL1
+    predv1: u8,
+    // PLL2MF[3:0], or `None` if CK_I2S is clocked by CK_SYS
+    pll2mf: Option<u8>,
+}
+
+// Search CK_SYS and CK_PLL2 * 2 for the CK_I2S frequency closest to `target`. If
+// `exact` is set, frequencies that are not whole hertz are skipped.
+//
+// PLL2 is clocked by HXTAL / PREDV1; if PLL1 is in use by `sys`, PREDV1 is kept.
+const fn search_ck_i2s(hxtal: Option<u32>, sys: SysSource, target: u32, exact: bool) -> I2sSource {
+    let mut best = I2sSource {
+        ck_i2s: sys.ck_sys,
+        predv1: sys.predv1,
+        pll2mf: None,
+    };
+    let hxtal = match hxtal {
+        Some(hxtal) => hxtal,
+        None => return best,
+    };
+    let (mut predv1, max_predv1) = match sys.pll1mf {
+        Some(_) => (sys.predv1 as u64 + 1, sys.predv1 as u64 + 1),
+        None => (1, 16),
+    };
+    while predv1 <= max_predv1 && best.ck_i2s != target {
+        let mut i = 0;
+        while i < PLL12MF.len() {
+            let (pll2mf, mul) = PLL12MF[i];
+            let num = 2 * hxtal as u64 * mul as u64;
+            let ck_i2s = num / predv1;
+            if (!exact || num % predv1 == 0)
+                && abs_diff(ck_i2s as u32, target) < abs_diff(best.ck_i2s, target)
+            {
+                best = I2sSource {
+                    ck_i2s: ck_i2s as u32,
+                    predv1: (predv1 - 1) as u8,
+                    pll2mf: Some(pll2mf),
+                };
+            }
+            i += 1;
+        }
+        predv1 += 1;
+    }
+    best
+}
+
+// Search the right shift closest to `target` that does not exceed `max`.
+const fn search_shr(freq: u32, target: u32, max: u32, shrs: &[u8]) -> u8 {
+    let mut best = 0;
+    let mut found = false;
+    let mut i = 0;
+    while i < shrs.len() {
+        let out = freq >> shrs[i];
+        if out <= max && (!found || abs_diff(out, target) < abs_diff(freq >> best, target)) {
+            best = shrs[i];
+            found = true;
+        }
+        i += 1;
+    }
+    if found {
+        best
+    } else {
+        // the largest shift as a fallback
+        shrs[shrs.len() - 1]
+    }
+}
+
+// Search the ADC prescaler closest to `target`, returns (ADCPSC, divider).
+const fn search_adcpsc(ck_apb2: u32, target: u32) -> (u8, u8) {
+    let (mut best_bits, mut best_div) = (0, 0);
+    let mut found = false;
+    let mut i = 0;
+    while i < ADC_DIV.len() {
+        let (bits, div) = ADC_DIV[i];
+        let ck_adc = ck_apb2 / div;
+        if ck_adc <= 14_000_000
+            && (!found || abs_diff(ck_adc, target) < abs_diff(ck_apb2 / best_div, target))
+        {
+            best_bits = bits;
+            best_div = div;
+            found = true;
+        }
+        i += 1;
+    }
+    // CK_APB2 / 16 never exceeds the limit
+    (best_bits, best_div as u8)
+}
+
+#[inline]
+const fn abs_diff(a: u32, b: u32) -> u32 {
+    if a > b {
+        a - b
+    } else {
+        b - a
+    }
+}
+
+/// Clock source of the CK_OUT0 output (CKOUT0SEL)
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClockOutputSource {
+    /// System clock (CK_SYS)
+    Sys,
+    /// Internal 8MHz oscillator (IRC8M)
+    Irc8m,
+    /// External high speed oscillator (HXTAL)
+    Hxtal,
+    /// PLL clock divided by 2 (CK_PLL / 2)
+    PllDiv2,
+    /// PLL1 clock (CK_PLL1)
+    Pll1,
+    /// PLL2 clock divided by 2 (CK_PLL2 / 2)
+    Pll2Div2,
+    /// EXT1 clock, i.e. HXTAL / PREDV1
+    Ext1,
+    /// PLL2 clock (CK_PLL2)
+    Pll2,
+}
+
+impl ClockOutputSource {
+    #[inline]
+    fn ckout0sel(self) -> u8 {
+        match self {
+            ClockOutputSource::Sys => 0b0100,
+            ClockOutputSource::Irc8m => 0b0101,
+            ClockOutputSource::Hxtal => 0b0110,
+            ClockOutputSource::PllDiv2 => 0b0111,
+            ClockOutputSource::Pll1 => 0b1000,
+            ClockOutputSource::Pll2Div2 => 0b1001,
+            ClockOutputSource::Ext1 => 0b1010,
+            ClockOutputSource::Pll2 => 0b1011,
+        }
+    }
+}
+
+/// Clock output (CK_OUT0) on PA8
+///
+/// The output clock is not divided, so it should be under the maximum speed of
+/// the pin. Sources which are not enabled output nothing.
+pub struct ClockOutput {
+    pin: PA8<Alternate<PushPull>>,
+}
+
+impl ClockOutput {
+    /// Route the clock `source` to PA8.
+    pub fn new(pin: PA8<Alternate<PushPull>>, source: ClockOutputSource, cfg: &mut CFG) -> Self {
+        let mut ans = ClockOutput { pin };
+        ans.set_source(source, cfg);
+        ans
+    }
+
+    /// Change the clock source of this output.
+    pub fn set_source(&mut self, source: ClockOutputSource, cfg: &mut CFG) {
+        cfg.cfg0()
+            .modify(|_, w| unsafe { w.ckout0sel().bits(source.ckout0sel()) });
+    }
+
+    /// Stop the clock output and release the pin.
+    pub fn release(self, cfg: &mut CFG) -> PA8<Alternate<PushPull>> {
+        // 00xx: no clock selected
+        cfg.cfg0()
+            .modify(|_, w| unsafe { w.ckout0sel().bits(0b0000) });
+        self.pin
+    }
+}
+
+/// Backup domain control register
+///
+/// Constrains `BDCTL`.
+///
+/// The backup domain is write protected after reset; split the `BKP` peripheral
+/// to enable write access before using these functions.
+pub struct BDCTL {
+    _ownership: (),
+}
+
+impl BDCTL {
+    #[inline]
+    pub(crate) fn bdctl(&mut self) -> &rcu::BDCTL {
+        unsafe { &(*RCU::ptr()).bdctl }
+    }
+
+    /// Enable the 32.768kHz external crystal oscillator (LXTAL), polling its
+    /// stabilization flag at most `max_polls` times.
+    ///
+    /// The drive capability of LXTAL is not configurable on this chip. If LXTAL
+    /// does not stabilize in time, this function turns it off and returns
+    /// `ClockError::LxtalTimeout`.
+    pub fn enable_lxtal(&mut self, max_polls: u32) -> Result<(), ClockError> {
+        self.start_lxtal(false, max_polls)
+    }
+
+    /// Enable LXTAL in bypass mode, where an external clock is provided on the
+    /// OSC32IN pin, polling its stabilization flag at most `max_polls` times.
+    ///
+    /// See `enable_lxtal` for details.
+    pub fn enable_lxtal_bypass(&mut self, max_polls: u32) -> Result<(), ClockError> {
+        self.start_lxtal(true, max_polls)
+    }
+
+    fn start_lxtal(&mut self, bypass: bool, max_polls: u32) -> Result<(), ClockError> {
+        let r = self.bdctl().read();
+        // LXTAL may be still running from before reset, e.g. clocking the RTC
+        if r.lxtalen().bit_is_set() && r.lxtalstb().bit_is_set() && r.lxtalbps().bit() == bypass {
+            return Ok(());
+        }
+        // LXTALBPS could only be written when LXTAL is disabled
+        self.bdctl().modify(|_, w| w.lxtalen().clear_bit());
+        self.bdctl().modify(|_, w| w.lxtalbps().bit(bypass));
+        self.bdctl().modify(|_, w| w.lxtalen().set_bit());
+        let stable = poll(Some(max_polls), || {
+            self.bdctl().read().lxtalstb().bit_is_set()
+        });
+        if stable {
+            Ok(())
+        } else {
+            self.disable_lxtal();
+            Err(ClockError::LxtalTimeout)
+        }
+    }
+
+    /// Disable the LXTAL oscillator.
+    pub fn disable_lxtal(&mut self) {
+        self.bdctl().modify(|_, w| w.lxtalen().clear_bit());
+    }
+
+    /// Returns whether LXTAL is stable (LXTALSTB).
+    pub fn is_lxtal_stable(&mut self) -> bool {
+        self.bdctl().read().lxtalstb().bit_is_set()
+    }
+}
+
+/// Reset source and clock register
+///
+/// Constrains `RSTSCK`.
+pub struct RSTSCK {
+    _ownership: (),
+}
+
+impl RSTSCK {
+    #[inline]
+    pub(crate) fn rstsck(&mut self) -> &rcu::RSTSCK {
+        unsafe { &(*RCU::ptr()).rstsck }
+    }
+
+    /// Enable the 40kHz internal oscillator (IRC40K), polling its stabilization
+    /// flag at most `max_polls` times.
+    ///
+    /// IRC40K clocks the free watchdog timer, and could be selected as the RTC clock.
+    /// If IRC40K does not stabilize in time, this function turns it off and returns
+    /// `ClockError::Irc40kTimeout`.
+    pub fn enable_irc40k(&mut self, max_polls: u32) -> Result<(), ClockError> {
+        self.rstsck().modify(|_, w| w.irc40ken().set_bit());
+        let stable = poll(Some(max_polls), || {
+            self.rstsck().read().irc40kstb().bit_is_set()
+        });
+        if stable {
+            Ok(())
+        } else {
+            self.disable_irc40k();
+            Err(ClockError::Irc40kTimeout)
+        }
+    }
+
+    /// Disable the IRC40K oscillator.
+    ///
+    /// IRC40K is forced on by hardware if the free watchdog timer is running.
+    pub fn disable_irc40k(&mut self) {
+        self.rstsck().modify(|_, w| w.irc40ken().clear_bit());
+    }
+
+    /// Returns whether IRC40K is stable (IRC40KSTB).
+    pub fn is_irc40k_stable(&mut self) -> bool {
+        self.rstsck().read().irc40kstb().bit_is_set()
+    }
+
+    /// Returns the reasons of previous resets.
+    ///
+    /// Reset flags are kept after following resets until they are cleared, so
+    /// there may be more than one reason in the returned set. Use `clear_reset_flags`
+    /// after reading to only get the reason of the next reset.
+    pub fn reset_reason(&mut self) -> ResetReason {
+        let r = self.rstsck().read();
+        let mut ans = ResetReason::empty();
+        if r.porrstf().bit_is_set() {
+            ans = ans | ResetReason::POWER;
+        }
+        if r.eprstf().bit_is_set() {
+            ans = ans | ResetReason::EXTERNAL_PIN;
+        }
+        if r.swrstf().bit_is_set() {
+            ans = ans | ResetReason::SOFTWARE;
+        }
+        if r.fwdgtrstf().bit_is_set() {
+            ans = ans | ResetReason::FWDGT;
+        }
+        if r.wwdgtrstf().bit_is_set() {
+            ans = ans | ResetReason::WWDGT;
+        }
+        if r.lprstf().bit_is_set() {
+            ans = ans | ResetReason::LOW_POWER;
+        }
+        ans
+    }
+
+    /// Clears all reset flags by setting the `RSTFC` bit.
+    pub fn clear_reset_flags(&mut self) {
+        self.rstsck().modify(|_, w| w.rstfc().set_bit());
+    }
+}
+
+/// Set of reset reasons, read from reset flags in `RSTSCK`
+///
+/// Ref: Section 5.3.10, the User Manual
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct ResetReason {
+    bits: u8,
+}
+
+impl ResetReason {
+    /// Power-on or power-down reset (PORRSTF)
+    pub const POWER: ResetReason = ResetReason { bits: 1 << 0 };
+    /// External pin reset on NRST (EPRSTF)
+    pub const EXTERNAL_PIN: ResetReason = ResetReason { bits: 1 << 1 };
+    /// Software reset (SWRSTF)
+    pub const SOFTWARE: ResetReason = ResetReason { bits: 1 << 2 };
+    /// Free watchdog timer reset (FWDGTRSTF)
+    pub const FWDGT: ResetReason = ResetReason { bits: 1 << 3 };
+    /// Window watchdog timer reset (WWDGTRSTF)
+    pub const WWDGT: ResetReason = ResetReason { bits: 1 << 4 };
+    /// Low-power management reset (LPRSTF)
+    pub const LOW_POWER: ResetReason = ResetReason { bits: 1 << 5 };
+
+    /// Returns an empty set
+    pub const fn empty() -> Self {
+        ResetReason { bits: 0 }
+    }
+
+    /// Returns whether no reset flag is set
+    pub const fn is_empty(&self) -> bool {
+        self.bits == 0
+    }
+
+    /// Returns whether all reasons in `other` are present in this set
+    pub const fn contains(&self, other: ResetReason) -> bool {
+        self.bits & other.bits == other.bits
+    }
+}
+
+impl core::ops::BitOr for ResetReason {
+    type Output = ResetReason;
+
+    fn bitor(self, rhs: ResetReason) -> ResetReason {
+        ResetReason {
+            bits: self.bits | rhs.bits,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const fn pll(predv0sel: bool, predv0: u8, pllmf: u8) -> PllConfig {
+        PllConfig {
+            pllsel: true,
+            predv0sel,
+            predv0,
+            pllmf,
+        }
+    }
+
+    // (HXTAL, CK_SYS, PLL, PREDV1, PLL1MF, USBFSPSC) with frequencies in MHz
+    type Expected = (u32, u32, PllConfig, u8, Option<u8>, Option<u8>);
+
+    const CRYSTALS: [Expected; 16] = [
+        (8, 48, pll(false, 0, 4), 0, None, Some(0b01)),
+        (8, 72, pll(false, 0, 7), 0, None, Some(0b00)),
+        (8, 96, pll(false, 0, 10), 0, None, Some(0b11)),
+        (8, 108, pll(false, 1, 26), 0, None, None),
+        (12, 48, pll(false, 0, 2), 0, None, Some(0b01)),
+        (12, 72, pll(false, 0, 4), 0, None, Some(0b00)),
+        (12, 96, pll(false, 0, 6), 0, None, Some(0b11)),
+        (12, 108, pll(false, 0, 7), 0, None, None),
+        (16, 48, pll(false, 0, 1), 0, None, Some(0b01)),
+        (16, 72, pll(false, 1, 7), 0, None, Some(0b00)),
+        (16, 96, pll(false, 0, 4), 0, None, Some(0b11)),
+        (16, 108, pll(false, 3, 26), 0, None, None),
+        (25, 48, pll(true, 4, 4), 4, Some(6), Some(0b01)),
+        (25, 72, pll(true, 4, 7), 4, Some(6), Some(0b00)),
+        (25, 96, pll(true, 4, 10), 4, Some(6), Some(0b11)),
+        (25, 108, pll(true, 9, 26), 4, Some(6), None),
+    ];
+
+    // Plans are computed by `const fn`s, so these are checked at compile time
+    const PLAN_108: ClockPlan = match (Strict {
+        hxtal: NonZeroU32::new(8_000_000),
+        target_ck_sys: NonZeroU32::new(108_000_000),
+        ..Strict::new()
+    })
+    .plan()
+    {
+        Ok(plan) => plan,
+        Err(_) => panic!("no plan for 108MHz from 8MHz HXTAL"),
+    };
+
+    const PRECISE_107: (ClockPlan, Deviation) = Precise {
+        hxtal: NonZeroU32::new(8_000_000),
+        target_ck_sys: NonZeroU32::new(107_000_000),
+        ..Precise::new()
+    }
+    .plan();
+
+    fn mhz(freq: u32) -> Hertz {
+        Hertz(freq * 1_000_000)
+    }
+
+    #[test]
+    fn strict_common_crystals() {
+        for &(hxtal, ck_sys, pll, predv1, pll1mf, usbfspsc) in CRYSTALS.iter() {
+            let plan = Strict::new().use_hxtal(mhz(hxtal)).ck_sys(mhz(ck_sys));
+            let plan = match plan.plan() {
+                Ok(plan) => plan,
+                Err(e) => panic!("{} MHz from {} MHz: {:?}", ck_sys, hxtal, e),
+            };
+            assert_eq!(plan.scs(), 0b10);
+            assert_eq!(plan.pll(), Some(pll));
+            assert_eq!(plan.predv1(), predv1);
+            assert_eq!(plan.pll1mf(), pll1mf);
+            assert_eq!(plan.pll2mf(), None);
+            assert_eq!(plan.usbfspsc(), usbfspsc);
+
+            let clocks = plan.clocks();
+            let ck_sys = ck_sys * 1_000_000;
+            assert_eq!(clocks.ck_sys().0, ck_sys);
+            assert_eq!(clocks.ck_ahb().0, ck_sys);
+            assert_eq!(clocks.ck_apb1().0, ck_sys / 2);
+            assert_eq!(clocks.ck_apb2().0, ck_sys);
+            assert_eq!(clocks.ck_adc().0, ck_sys / 8);
+            assert_eq!(clocks.ck_i2s1().0, ck_sys);
+            assert_eq!(clocks.ck_usbfs_valid(), usbfspsc.is_some());
+        }
+    }
+
+    #[test]
+    fn strict_errors() {
+        let sys = ClockDomain::Sys;
+        let usb = ClockDomain::Usb;
+        let i2s = ClockDomain::I2s;
+
+        let plan = Strict::new().use_hxtal(mhz(8)).ck_sys(Hertz(100_000));
+        assert_eq!(plan.plan().err(), Some(ClockError::PllMultiplier(sys)));
+        let plan = Strict::new().ck_sys(mhz(4));
+        assert_eq!(plan.plan().err(), Some(ClockError::PllMultiplier(sys)));
+
+        let plan = Strict::new().use_hxtal(mhz(8)).ck_sys(mhz(107));
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(sys)));
+        let plan = Strict::new().ck_sys(mhz(107));
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(sys)));
+
+        let plan = Strict::new()
+            .use_hxtal(mhz(8))
+            .ck_sys(mhz(108))
+            .require_usbfs();
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(usb)));
+        let plan = Strict::new().ck_sys(mhz(48)).require_usbfs();
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(usb)));
+
+        let plan = Strict::new().use_hxtal(mhz(8)).ck_sys(mhz(108));
+        let plan = plan.ck_i2s(Hertz(1_000_003));
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(i2s)));
+        let plan = Strict::new().ck_sys(mhz(48)).ck_i2s(mhz(96));
+        assert_eq!(plan.plan().err(), Some(ClockError::NoDivider(i2s)));
+    }
+
+    #[test]
+    fn const_plans() {
+        assert_eq!(PLAN_108.scs(), 0b10);
+        assert_eq!(PLAN_108.pll(), Some(pll(false, 1, 26)));
+        assert_eq!(PLAN_108.clocks().ck_sys().0, 108_000_000);
+
+        let (plan, deviation) = PRECISE_107;
+        assert_eq!(plan.pll(), Some(pll(true, 12, 28)));
+        assert_eq!(plan.clocks().ck_sys().0, 107_076_923);
+        assert_eq!(deviation.ck_sys, 76_923);
+        assert_eq!(deviation.ck_ahb, 0);
+    }
+
+    #[test]
+    fn strict_default_is_irc8m() {
+        let plan = match Strict::new().plan() {
+            Ok(plan) => plan,
+            Err(e) => panic!("{:?}", e),
+        };
+        assert!(!plan.hxtal());
+        assert_eq!(plan.scs(), 0b00);
+        assert_eq!(plan.pll(), None);
+        assert_eq!(plan.clocks().ck_sys().0, 8_000_000);
+        assert!(!plan.clocks().ck_usbfs_valid());
+    }
+
+    #[test]
+    fn precise_nearest() {
+        let (plan, deviation) = Precise::new().ck_sys(Hertz(50_000_000)).plan();
+        let irc8m = PllConfig {
+            pllsel: false,
+            ..pll(false, 0, 10)
+        };
+        assert_eq!(plan.pll(), Some(irc8m));
+        assert_eq!(plan.clocks().ck_sys().0, 48_000_000);
+        assert_eq!(deviation.ck_sys, -2_000_000);
+
+        let (plan, deviation) = Precise::new().use_hxtal(mhz(25)).ck_sys(mhz(108)).plan();
+        assert_eq!(plan.pll(), Some(pll(true, 9, 26)));
+        assert_eq!(plan.predv1(), 4);
+        assert_eq!(plan.pll1mf(), Some(6));
+        assert_eq!(plan.clocks().ck_sys().0, 108_000_000);
+        assert_eq!(deviation, Deviation::default());
     }
 }
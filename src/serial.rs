@@ -175,9 +175,13 @@ macro_rules! sprint {
 
 // use crate::pac::USART0;
 use crate::afio::PCF0;
-use crate::gpio::gpioa::{PA10, PA9};
+use crate::gpio::gpioa::{PA10, PA2, PA3, PA9};
+use crate::gpio::gpiob::{PB10, PB11, PB6, PB7};
+use crate::gpio::gpioc::{PC10, PC11, PC12};
+use crate::gpio::gpiod::{PD2, PD5, PD6, PD8, PD9};
 use crate::gpio::{Alternate, Floating, Input, PushPull};
-use crate::rcu::{Clocks, APB2};
+use crate::pac::{UART3, UART4, USART1, USART2};
+use crate::rcu::{Clocks, Enable, Reset, APB1, APB2};
 use crate::time::Bps;
 
 /// Serial config
@@ -241,74 +245,16 @@ impl StopBits {
 pub struct Serial<USART, PINS> {
     usart: USART,
     pins: PINS,
+    baudrate: u32,
 }
 
-impl<PINS> Serial<USART0, PINS> {
-    /// Power on and create serial instance
-    pub fn usart0(
-        usart0: USART0,
-        pins: PINS,
-        pcf0: &mut PCF0,
-        config: Config,
-        clocks: Clocks,
-        apb2: &mut APB2,
-    ) -> Self
-    where
-        PINS: Pins<USART0>,
-    {
-        // calculate baudrate divisor fractor
-        let (intdiv, fradiv) = {
-            // use apb2 or apb1 may vary
-            // round the value to get most accurate one (without float point)
-            let baud_div = (clocks.ck_apb2().0 + config.baudrate.0 / 2) / config.baudrate.0;
-            assert!(baud_div <= 0xFFFF, "impossible baudrate");
-            ((baud_div & 0xFFF0) as u16, (baud_div & 0x0F) as u8)
-        };
-        // get parity config
-        let (wl, pcen, pm) = config.parity.config();
-        // get stop bit config
-        let stb = config.stop_bits.config();
-        riscv::interrupt::free(|_| {
-            // enable and reset usart peripheral
-            apb2.en().modify(|_, w| w.usart0en().set_bit());
-            apb2.rst().modify(|_, w| w.usart0rst().set_bit());
-            apb2.rst().modify(|_, w| w.usart0rst().clear_bit());
-            // set serial remap
-            pcf0.pcf0()
-                .modify(|_, w| w.usart0_remap().bit(PINS::REMAP == 1));
-            // does not enable DMA in this section; DMA is enabled separately
-            // set baudrate
-            usart0
-                .baud
-                .write(|w| unsafe { w.intdiv().bits(intdiv).fradiv().bits(fradiv) });
-            // configure stop bits
-            usart0.ctl1.modify(|_, w| unsafe { w.stb().bits(stb) });
-            usart0.ctl0.modify(|_, w| {
-                // set parity check settings
-                w.wl().bit(wl).pcen().bit(pcen).pm().bit(pm);
-                // enable the peripheral
-                // todo: split receive and transmit
-                w.uen().set_bit().ren().set_bit().ten().set_bit()
-            });
-        });
-        Serial {
-            usart: usart0,
-            pins,
-        }
-    }
-
-    /// Power down and return ownership of owned registers
-    pub fn release(self, apb2: &mut APB2) -> (USART0, PINS) {
-        // disable the peripheral
-        self.usart
-            .ctl0
-            .modify(|_, w| w.uen().clear_bit().ren().clear_bit().ten().clear_bit());
-        // disable the clock
-        apb2.en().modify(|_, w| w.usart0en().clear_bit());
-
-        // return the ownership
-        (self.usart, self.pins)
-    }
+// Calculate the baudrate divisor of (INTDIV, FRADIV) from the peripheral clock
+#[inline]
+fn baud_div(ck_apb: u32, baudrate: u32) -> (u16, u8) {
+    // round the value to get most accurate one (without float point)
+    let baud_div = (ck_apb + baudrate / 2) / baudrate;
+    assert!(baud_div <= 0xFFFF, "impossible baudrate");
+    ((baud_div & 0xFFF0) as u16, (baud_div & 0x0F) as u8)
 }
 
 /// Serial error
@@ -323,87 +269,210 @@ pub enum Error {
     Parity,
 }
 
-impl<PINS> embedded_hal::serial::Read<u8> for Serial<USART0, PINS> {
-    type Error = Error;
-
-    fn read(&mut self) -> nb::Result<u8, Self::Error> {
-        let stat = self.usart.stat.read();
-        // the chip has already filled data buffer with input data
-        // check for errors present
-        let err = if stat.orerr().bit_is_set() {
-            Some(Error::Overrun)
-        } else if stat.nerr().bit_is_set() {
-            Some(Error::Noise)
-        } else if stat.ferr().bit_is_set() {
-            Some(Error::Framing)
-        } else if stat.perr().bit_is_set() {
-            Some(Error::Parity)
-        } else {
-            None
-        };
+// Writes the remap bits of each U(S)ART in PCF0
+trait Remap {
+    fn remap(pcf0: &mut PCF0, remap: u8);
+}
 
-        if let Some(err) = err {
-            // error occurred, no data is read. clean the data buffer and error flags
-            // note(unsafe): stateless register read
-            unsafe {
-                core::ptr::read_volatile(&self.usart.stat as *const _ as *const _);
-                core::ptr::read_volatile(&self.usart.data as *const _ as *const _);
-            }
-            // returns error; no data is returned
-            Err(nb::Error::Other(err))
-        } else {
-            // if a byte is available, return the byte; or the upstream should wait
-            // until a byte is ready
-            if stat.rbne().bit_is_set() {
-                // read buffer non empty, return this byte
-                Ok(unsafe { core::ptr::read_volatile(&self.usart.data as *const _ as *const _) })
-            } else {
-                // byte is not ready
-                Err(nb::Error::WouldBlock)
-            }
-        }
+impl Remap for USART0 {
+    fn remap(pcf0: &mut PCF0, remap: u8) {
+        pcf0.modify(|_, w| w.usart0_remap().bit(remap == 1));
     }
 }
 
-impl<PINS> embedded_hal::serial::Write<u8> for Serial<USART0, PINS> {
-    type Error = core::convert::Infallible; // !
+impl Remap for USART1 {
+    fn remap(pcf0: &mut PCF0, remap: u8) {
+        pcf0.modify(|_, w| w.usart1_remap().bit(remap == 1));
+    }
+}
 
-    fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
-        let stat = self.usart.stat.read();
+impl Remap for USART2 {
+    fn remap(pcf0: &mut PCF0, remap: u8) {
+        pcf0.modify(|_, w| unsafe { w.usart2_remap().bits(remap) });
+    }
+}
 
-        if stat.tbe().bit_is_set() {
-            // NOTE(unsafe) atomic write to stateless register
-            // impossible using PAC only to write u8 value
-            unsafe {
-                // compiles into `lui a?, %hi(USART_DATA); sb a??, %lo(USART_DATA)(a?)`
-                core::ptr::write_volatile(&self.usart.data as *const _ as *mut _, byte)
+// UART3 and UART4 could not be remapped
+impl Remap for UART3 {
+    fn remap(_: &mut PCF0, _: u8) {}
+}
+
+impl Remap for UART4 {
+    fn remap(_: &mut PCF0, _: u8) {}
+}
+
+macro_rules! serial {
+    ($($USARTX:ident: ($usartX:ident, $APBX:ident, $pclkX:ident),)+) => {
+        $(
+            impl<PINS> Serial<$USARTX, PINS> {
+                /// Power on and create serial instance
+                pub fn $usartX(
+                    usart: $USARTX,
+                    pins: PINS,
+                    pcf0: &mut PCF0,
+                    config: Config,
+                    clocks: Clocks,
+                    apb: &mut $APBX,
+                ) -> Self
+                where
+                    PINS: Pins<$USARTX>,
+                {
+                    // calculate baudrate divisor fractor
+                    let (intdiv, fradiv) = baud_div(clocks.$pclkX().0, config.baudrate.0);
+                    // get parity config
+                    let (wl, pcen, pm) = config.parity.config();
+                    // get stop bit config
+                    let stb = config.stop_bits.config();
+                    riscv::interrupt::free(|_| {
+                        // enable and reset usart peripheral
+                        $USARTX::enable(apb);
+                        $USARTX::reset(apb);
+                        // set serial remap
+                        $USARTX::remap(pcf0, PINS::REMAP);
+                        // does not enable DMA in this section; DMA is enabled separately
+                        // set baudrate
+                        usart
+                            .baud
+                            .write(|w| unsafe { w.intdiv().bits(intdiv).fradiv().bits(fradiv) });
+                        // configure stop bits
+                        usart.ctl1.modify(|_, w| unsafe { w.stb().bits(stb) });
+                        usart.ctl0.modify(|_, w| {
+                            // set parity check settings
+                            w.wl().bit(wl).pcen().bit(pcen).pm().bit(pm);
+                            // enable the peripheral
+                            // only enable receive and transmit with pins present
+                            w.uen().set_bit();
+                            w.ren().bit(PINS::RX).ten().bit(PINS::TX)
+                        });
+                    });
+                    Serial {
+                        usart,
+                        pins,
+                        baudrate: config.baudrate.0,
+                    }
+                }
+
+                /// Recalculate the baudrate divisor after the clocks are switched.
+                ///
+                /// Wait for the ongoing transmission to complete before calling this function.
+                pub fn reconfigure(&mut self, clocks: Clocks) {
+                    let (intdiv, fradiv) = baud_div(clocks.$pclkX().0, self.baudrate);
+                    // baudrate should be changed when the peripheral is disabled
+                    self.usart.ctl0.modify(|_, w| w.uen().clear_bit());
+                    self.usart
+                        .baud
+                        .write(|w| unsafe { w.intdiv().bits(intdiv).fradiv().bits(fradiv) });
+                    self.usart.ctl0.modify(|_, w| w.uen().set_bit());
+                }
+
+                /// Power down and return ownership of owned registers
+                pub fn release(self, apb: &mut $APBX) -> ($USARTX, PINS) {
+                    // disable the peripheral
+                    self.usart
+                        .ctl0
+                        .modify(|_, w| w.uen().clear_bit().ren().clear_bit().ten().clear_bit());
+                    // disable the clock
+                    $USARTX::disable(apb);
+
+                    // return the ownership
+                    (self.usart, self.pins)
+                }
             }
-            Ok(())
-        } else {
-            // upstream should wait until end of transmit
-            Err(nb::Error::WouldBlock)
-        }
-    }
 
-    fn flush(&mut self) -> nb::Result<(), Self::Error> {
-        // if translate completed, do not wait
-        if self.usart.stat.read().tc().bit_is_set() {
-            Ok(())
-        } else {
-            // otherwise upstream should wait
-            Err(nb::Error::WouldBlock)
-        }
+            impl<PINS> embedded_hal::serial::Read<u8> for Serial<$USARTX, PINS> {
+                type Error = Error;
+
+                fn read(&mut self) -> nb::Result<u8, Self::Error> {
+                    let stat = self.usart.stat.read();
+                    // the chip has already filled data buffer with input data
+                    // check for errors present
+                    let err = if stat.orerr().bit_is_set() {
+                        Some(Error::Overrun)
+                    } else if stat.nerr().bit_is_set() {
+                        Some(Error::Noise)
+                    } else if stat.ferr().bit_is_set() {
+                        Some(Error::Framing)
+                    } else if stat.perr().bit_is_set() {
+                        Some(Error::Parity)
+                    } else {
+                        None
+                    };
+
+                    if let Some(err) = err {
+                        // error occurred, no data is read. clean the data buffer and error flags
+                        // note(unsafe): stateless register read
+                        unsafe {
+                            core::ptr::read_volatile(&self.usart.stat as *const _ as *const _);
+                            core::ptr::read_volatile(&self.usart.data as *const _ as *const _);
+                        }
+                        // returns error; no data is returned
+                        Err(nb::Error::Other(err))
+                    } else {
+                        // if a byte is available, return the byte; or the upstream should wait
+                        // until a byte is ready
+                        if stat.rbne().bit_is_set() {
+                            // read buffer non empty, return this byte
+                            let data = &self.usart.data as *const _ as *const _;
+                            Ok(unsafe { core::ptr::read_volatile(data) })
+                        } else {
+                            // byte is not ready
+                            Err(nb::Error::WouldBlock)
+                        }
+                    }
+                }
+            }
+
+            impl<PINS> embedded_hal::serial::Write<u8> for Serial<$USARTX, PINS> {
+                type Error = core::convert::Infallible; // !
+
+                fn write(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+                    let stat = self.usart.stat.read();
+
+                    if stat.tbe().bit_is_set() {
+                        // NOTE(unsafe) atomic write to stateless register
+                        // impossible using PAC only to write u8 value
+                        unsafe {
+                            // compiles into `lui a?, %hi(USART_DATA); sb a??, %lo(USART_DATA)(a?)`
+                            let data = core::ptr::addr_of!((*$USARTX::ptr()).data) as *mut u8;
+                            core::ptr::write_volatile(data, byte)
+                        }
+                        Ok(())
+                    } else {
+                        // upstream should wait until end of transmit
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+
+                fn flush(&mut self) -> nb::Result<(), Self::Error> {
+                    // if translate completed, do not wait
+                    if self.usart.stat.read().tc().bit_is_set() {
+                        Ok(())
+                    } else {
+                        // otherwise upstream should wait
+                        Err(nb::Error::WouldBlock)
+                    }
+                }
+            }
+
+            impl<PINS> core::fmt::Write for Serial<$USARTX, PINS> {
+                fn write_str(&mut self, s: &str) -> core::fmt::Result {
+                    use embedded_hal::serial::Write;
+                    s.as_bytes()
+                        .iter()
+                        .try_for_each(|c| nb::block!(self.write(*c)))
+                        .map_err(|_| core::fmt::Error) // no write error is possible
+                }
+            }
+        )+
     }
 }
 
-impl<PINS> core::fmt::Write for Serial<USART0, PINS> {
-    fn write_str(&mut self, s: &str) -> core::fmt::Result {
-        use embedded_hal::serial::Write;
-        s.as_bytes()
-            .iter()
-            .try_for_each(|c| nb::block!(self.write(*c)))
-            .map_err(|_| core::fmt::Error) // no write error is possible
-    }
+serial! {
+    USART0: (usart0, APB2, ck_apb2),
+    USART1: (usart1, APB1, ck_apb1),
+    USART2: (usart2, APB1, ck_apb1),
+    UART3: (uart3, APB1, ck_apb1),
+    UART4: (uart4, APB1, ck_apb1),
 }
 
 // /// IrDA Config
@@ -450,13 +519,55 @@ impl<PINS> IrDA<USART0, PINS> {
     }
 }
 
+/// Serial pins
+///
+/// Implemented for tuples of (TX, RX) pins; use `NoTx` or `NoRx` in place of an
+/// unused pin for receive-only or transmit-only serials.
 pub trait Pins<USART> {
     #[doc(hidden)] // internal use only
     const REMAP: u8;
+    #[doc(hidden)]
+    const TX: bool = true;
+    #[doc(hidden)]
+    const RX: bool = true;
+}
+
+/// Placeholder of the TX pin for receive-only serials
+pub struct NoTx;
+
+/// Placeholder of the RX pin for transmit-only serials
+pub struct NoRx;
+
+macro_rules! pins {
+    ($($USART:ident: $REMAP:expr => ($TX:ident, $RX:ident),)+) => {
+        $(
+            impl Pins<$USART> for ($TX<Alternate<PushPull>>, $RX<Input<Floating>>) {
+                const REMAP: u8 = $REMAP;
+            }
+
+            impl Pins<$USART> for ($TX<Alternate<PushPull>>, NoRx) {
+                const REMAP: u8 = $REMAP;
+                const RX: bool = false;
+            }
+
+            impl Pins<$USART> for (NoTx, $RX<Input<Floating>>) {
+                const REMAP: u8 = $REMAP;
+                const TX: bool = false;
+            }
+        )+
+    };
 }
 
-impl Pins<USART0> for (PA9<Alternate<PushPull>>, PA10<Input<Floating>>) {
-    const REMAP: u8 = 0;
+pins! {
+    USART0: 0 => (PA9, PA10),
+    USART0: 1 => (PB6, PB7),
+    USART1: 0 => (PA2, PA3),
+    USART1: 1 => (PD5, PD6),
+    USART2: 0b00 => (PB10, PB11),
+    USART2: 0b01 => (PC10, PC11), // partial remap
+    USART2: 0b11 => (PD8, PD9),   // full remap
+    UART3: 0 => (PC10, PC11),
+    UART4: 0 => (PC12, PD2),
 }
 
 //todo
@@ -3,7 +3,7 @@ use crate::gpio::gpioa::*;
 use crate::gpio::gpiob::*;
 use crate::gpio::{Alternate, Floating, Input, Output, PushPull};
 use crate::pac::{SPI0, SPI1, SPI2};
-use crate::rcu::{Clocks, APB1, APB2};
+use crate::rcu::{Clocks, Enable, Reset, APB1, APB2};
 use crate::time::Hertz;
 use embedded_hal::blocking::spi::*;
 pub use embedded_hal::spi::{FullDuplex, Mode, Phase, Polarity};
@@ -25,6 +25,21 @@ pub enum Error {
 pub struct Spi<SPI, PINS> {
     spi: SPI,
     pins: PINS,
+    freq: Hertz,
+}
+
+// PSC[2:0] bits to divide the peripheral clock into at most `freq`
+#[inline]
+fn prescaler_bits(ck_apb: Hertz, freq: Hertz) -> u8 {
+    // round up, so that the divided clock never exceeds `freq`
+    let ratio = match ck_apb.0.checked_div(freq.0) {
+        Some(ratio) if ck_apb.0 % freq.0 != 0 => ratio + 1,
+        Some(ratio) => ratio,
+        None => u32::max_value(),
+    };
+    // the smallest divider 2 << PSC not less than `ratio`, saturating at /2 and /256
+    let log2 = 32 - ratio.saturating_sub(1).leading_zeros();
+    (log2.max(1).min(8) - 1) as u8
 }
 
 #[doc(hidden)]
@@ -62,7 +77,7 @@ macro_rules! pins {
 }
 
 macro_rules! spi {
-    ($($SPIX:ident: ($spiX:ident, $APBX:ident, $spiXen:ident, $spiXrst:ident, $pclkX:ident),)+) => {
+    ($($SPIX:ident: ($spiX:ident, $APBX:ident, $pclkX:ident),)+) => {
         $(
             impl<SCK, MISO, MOSI, NSS> Spi<$SPIX, (SCK, MISO, MOSI, NSS)> {
                 /// Configures the SPI peripheral to operate in full duplex master mode
@@ -81,22 +96,11 @@ macro_rules! spi {
                     MOSI: MosiPin<$SPIX>,
                     NSS: NssPin<$SPIX>
                 {
+                    let freq = freq.into();
+                    let prescaler_bits = prescaler_bits(clocks.$pclkX(), freq);
 
-                    let prescaler_bits = match clocks.$pclkX().0 / freq.into().0 {
-                        0 => unreachable!(),
-                        2..=2 => 0b000,
-                        4..=5 => 0b001,
-                        8..=11 => 0b010,
-                        16..=23 => 0b011,
-                        32..=39 => 0b100,
-                        64..=95 => 0b101,
-                        128..=191 => 0b110,
-                        _ => 0b111,
-                    };
-
-                    apb.en().modify(|_,w| w.$spiXen().set_bit());
-                    //apb.rst().write(|w| w.$spiXrst().set_bit());
-                    //apb.rst().write(|w| w.$spiXrst().clear_bit());
+                    $SPIX::enable(apb);
+                    $SPIX::reset(apb);
 
                     spi.ctl0.write(|w| w.spien().clear_bit()); //disable while configuring...
                     spi.ctl1.modify(|_,w| w.nssdrv().clear_bit()); //let application drive the nss pin.
@@ -118,11 +122,23 @@ macro_rules! spi {
                     }
 
 
-                    Spi { spi, pins }
+                    Spi { spi, pins, freq }
+                }
+
+                /// Recalculate the SPI prescaler after the clocks are switched.
+                ///
+                /// Wait for the ongoing transfer to complete before calling this function.
+                pub fn reconfigure(&mut self, clocks: Clocks) {
+                    let prescaler_bits = prescaler_bits(clocks.$pclkX(), self.freq);
+                    self.spi.ctl0.modify(|_, w| w.spien().clear_bit());
+                    self.spi.ctl0.modify(|_, w| unsafe { w.psc().bits(prescaler_bits) });
+                    self.spi.ctl0.modify(|_, w| w.spien().set_bit());
                 }
 
-                /// Releases the SPI peripheral and associated pins
-                pub fn free(self) -> ($SPIX, (SCK, MISO, MOSI, NSS)) {
+                /// Disables the SPI clock, releases the SPI peripheral and associated pins
+                pub fn free(self, apb: &mut $APBX) -> ($SPIX, (SCK, MISO, MOSI, NSS)) {
+                    self.spi.ctl0.modify(|_, w| w.spien().clear_bit());
+                    $SPIX::disable(apb);
                     (self.spi, self.pins)
                 }
             }
@@ -181,7 +197,7 @@ pins! {SPI2,
 }
 
 spi! {
-    SPI0: (spi0, APB2, spi0en, spi0rst, ck_apb2),
-    SPI1: (spi1, APB1, spi1en, spi1rst, ck_apb1),
-    SPI2: (spi2, APB1, spi2en, spi2rst, ck_apb1),
+    SPI0: (spi0, APB2, ck_apb2),
+    SPI1: (spi1, APB1, ck_apb1),
+    SPI2: (spi2, APB1, ck_apb1),
 }
@@ -1,6 +1,6 @@
 //! Timers
 use crate::pac::TIMER6;
-use crate::rcu::{Clocks, APB1};
+use crate::rcu::{Clocks, Enable, RcuBus, Reset, APB1};
 use crate::time::Hertz;
 use embedded_hal::blocking::delay::DelayMs;
 use embedded_hal::timer::CountDown;
@@ -19,25 +19,30 @@ impl Timer<TIMER6> {
     /// An enable and reset procedure is procceed to peripheral to clean its state.
     pub fn timer6(timer: TIMER6, clock: Clocks, apb1: &mut APB1) -> Self {
         riscv::interrupt::free(|_| {
-            apb1.en().modify(|_, w| w.timer6en().set_bit());
-            apb1.rst().write(|w| w.timer6rst().set_bit());
-            apb1.rst().write(|w| w.timer6rst().clear_bit());
+            TIMER6::enable(apb1);
+            TIMER6::reset(apb1);
         });
         Timer {
             timer,
             clock_scaler: 1000,
-            clock_frequency: clock.ck_apb1(),
+            clock_frequency: clock.ck_timer_apb1(),
         }
     }
+
+    /// Update the timer clock frequency after the clocks are switched.
+    pub fn reconfigure(&mut self, clock: Clocks) {
+        self.clock_frequency = clock.ck_timer_apb1();
+    }
 }
 
-impl<TIMER> Timer<TIMER> {
+impl<TIMER: Enable> Timer<TIMER> {
     // in future designs we do not stop timer in this function
     // but prefer using Timer<TIMER>::start(self, ...) -> SomeTimer
     // when SomeTimer should be stopped, it has function returns timer back
     // as SomeTimer::stop(self) -> Timer<TIMER>.
-    /// Release the timer, return its ownership.
-    pub fn release(self) -> TIMER {
+    /// Disable the timer clock and return its ownership.
+    pub fn release(self, bus: &mut <TIMER as RcuBus>::Bus) -> TIMER {
+        TIMER::disable(bus);
         self.timer
     }
 }
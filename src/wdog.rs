@@ -21,6 +21,9 @@ const FWDGT_MAX_RLD: u16 = 0xFFF;
 ///
 /// This watchdog timer cannot be disabled.
 ///
+/// The watchdog counter is clocked by IRC40K; use `RSTSCK::enable_irc40k` to
+/// make sure the oscillator is stable before starting the watchdog.
+///
 /// TODO: debug
 pub struct Free {
     fwdgt: FWDGT,